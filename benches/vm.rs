@@ -0,0 +1,65 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compares the tree-walking evaluator with the bytecode vm on a few recursive workloads
+//!
+//! Run with `cargo bench --bench vm`
+
+use std::{
+    cell::RefCell,
+    io,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use faye::{eval::ContextBuilder, prelude::Parser};
+
+const RUNS: u32 = 10;
+
+const WORKLOADS: [(&str, &str, &str); 3] = [
+    (
+        "fib",
+        "(fn fib [n] (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))",
+        "(fib 20)",
+    ),
+    (
+        "quicksort",
+        include_str!("../examples/quicksort.fy"),
+        "(strlist (quicksort (consify [5 1 2 6 3 9 7 10 8 4 15 11 13 12 14])))",
+    ),
+    (
+        "closures",
+        "(fn count [f n acc] (if (= n 0) acc (count f (- n 1) (f acc))))",
+        "(count (lambda [x] (+ x 1)) 500 0)",
+    ),
+];
+
+fn time(runs: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        f();
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    for (name, setup, expr) in WORKLOADS {
+        let setup = Parser::new(setup).parse().expect("setup parses");
+        let expr = &Parser::new(expr).parse().expect("workload parses")[0];
+        let mut ctx = ContextBuilder::all()
+            .stdout(Rc::new(RefCell::new(io::sink())))
+            .build();
+        ctx.check(&setup).map_or(Ok(()), Err).expect("setup runs");
+
+        let tree = time(RUNS, || _ = ctx.eval(expr).expect("tree-walker runs"));
+        let chunk = ctx.compile(expr);
+        let vm = time(RUNS, || _ = ctx.run(&chunk).expect("vm runs"));
+
+        println!(
+            "{name:10} tree-walker {tree:>10.2?}  vm {vm:>10.2?}  {:.1}x",
+            tree.as_secs_f64() / vm.as_secs_f64()
+        );
+    }
+}
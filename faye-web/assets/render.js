@@ -10,3 +10,5 @@ export const scroll = (el) => el.scrollIntoView();
 
 export const set_cursor = (el, position) =>
     window.getSelection().collapse(el, position);
+
+export const prompt_line = (message) => window.prompt(message);
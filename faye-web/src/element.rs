@@ -1,4 +1,6 @@
-use maud::Render;
+use std::io::Write;
+
+use maud::{html, Render};
 use wasm_bindgen::prelude::*;
 
 use crate::renderer;
@@ -43,3 +45,41 @@ impl Element {
         renderer::set_cursor(&self.inner, position);
     }
 }
+
+/// A sink that appends the output of a program to an element as text
+pub struct ElementWriter {
+    element: Element,
+    error: bool,
+}
+
+impl ElementWriter {
+    pub const fn stdout(element: Element) -> Self {
+        Self {
+            element,
+            error: false,
+        }
+    }
+
+    pub const fn stderr(element: Element) -> Self {
+        Self {
+            element,
+            error: true,
+        }
+    }
+}
+
+impl Write for ElementWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        if self.error {
+            self.element.push(html! { span.faye-error { (text) } });
+        } else {
+            self.element.push(html! { (text) });
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -12,6 +12,7 @@ fn display_error(span: &Span, err: &impl std::error::Error) -> Markup {
 }
 
 pub fn eval(ctx: &mut Context, expr: &str) -> Markup {
+    ctx.reset_budget();
     let mut parser = Parser::new(expr);
 
     let ast = match parser.parse() {
@@ -1,4 +1,4 @@
-use faye::prelude::*;
+use faye::{highlighter::DatumComments, prelude::*};
 use maud::{html, Markup};
 
 pub fn highlight(snippet: &str) -> Markup {
@@ -6,10 +6,12 @@ pub fn highlight(snippet: &str) -> Markup {
 
     let mut is_fn = false;
     let mut start = 0;
+    let mut comments = DatumComments::default();
     for res in Lexer::new(snippet) {
         let class = match &res {
+            Ok(t) if comments.skips(&t.kind) => "faye-comment",
             Ok(t) => match t.kind {
-                TokenKind::Comment(_) => "faye-comment",
+                TokenKind::Comment(_) | TokenKind::DatumComment => "faye-comment",
                 TokenKind::OpenParen
                 | TokenKind::CloseParen
                 | TokenKind::OpenBracket
@@ -17,6 +19,7 @@ pub fn highlight(snippet: &str) -> Markup {
                 TokenKind::Number(_) => "faye-number",
                 TokenKind::Bool(_) => "faye-bool",
                 TokenKind::String(_) | TokenKind::Char(_) => "faye-string",
+                TokenKind::Regex(_) => "faye-regex",
                 TokenKind::Symbol(_) if is_fn => "faye-symbol-call",
                 TokenKind::Symbol(_) => "faye-symbol",
                 TokenKind::Keyword(_) => "faye-keyword",
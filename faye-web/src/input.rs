@@ -0,0 +1,37 @@
+use std::io::{BufRead, Read};
+
+use crate::renderer;
+
+/// A source of input that asks the visitor for each line with a browser prompt
+#[derive(Default)]
+pub struct PromptReader {
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for PromptReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for PromptReader {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos >= self.line.len() {
+            // cancelling the prompt ends the input
+            let Some(line) = renderer::prompt_line("faye is waiting for input") else {
+                return Ok(&[]);
+            };
+            self.line = format!("{line}\n").into_bytes();
+            self.pos = 0;
+        }
+
+        Ok(&self.line[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
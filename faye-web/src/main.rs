@@ -1,17 +1,21 @@
+use std::{cell::RefCell, rc::Rc};
+
 use faye::prelude::*;
 use maud::html;
 use web_sys::{Event, KeyboardEvent};
 
+use element::ElementWriter;
 use eval::eval;
 use highlight::highlight;
+use input::PromptReader;
 
 mod element;
 mod eval;
 mod highlight;
+mod input;
 mod renderer;
 
 fn main() {
-    let mut ctx = Context::default();
     let root = element::Element::root();
 
     let mut input_history = Vec::<String>::new();
@@ -32,6 +36,24 @@ fn main() {
     });
 
     let history = terminal.push(html! { .history {} });
+
+    // scripts run in the visitor's browser, so they only get to compute, print and prompt,
+    // and runaway ones are stopped before they freeze the tab. the browser's stack is about 1MB,
+    // which deep recursion overflows well before the step limit is reached
+    let mut ctx = ContextBuilder::pure()
+        .capability(Capability::Io)
+        .step_limit(1_000_000)
+        .allocation_limit(10_000_000)
+        .depth_limit(200)
+        .stdout(Rc::new(RefCell::new(ElementWriter::stdout(
+            history.clone(),
+        ))))
+        .stderr(Rc::new(RefCell::new(ElementWriter::stderr(
+            history.clone(),
+        ))))
+        .stdin(Rc::new(RefCell::new(PromptReader::default())))
+        .build();
+
     let command = terminal.push(html! { #command { span.prompt { "λ " } } });
     let cmd_display = command.push(html! {
         span #command_display {}
@@ -85,6 +107,12 @@ fn main() {
                     cmd_display.update("");
                     help_hint.inner.set_hidden(true);
 
+                    // printed output is appended to the history while evaluating, so the
+                    // prompt goes in first
+                    history.push(html! {
+                        span.prompt { "λ " } (highlight(&input)) br;
+                    });
+
                     // builtin playground commands
                     let output = match input.as_str() {
                         "clear" => {
@@ -107,7 +135,7 @@ fn main() {
                             br;
                             span.faye-symbol-call {
                                 @for (i, sym) in ctx.list_globals().into_iter().enumerate() {
-                                    @let s = sym.0;
+                                    @let s = sym.name();
                                     @match (12_usize).checked_sub(s.chars().count()) {
                                         Some(w) if i % 4 != 3 => { (s) (" ".repeat(w + 4)) },
                                         _ => { (s) br; }
@@ -122,10 +150,7 @@ fn main() {
                         s => eval(&mut ctx, s),
                     };
 
-                    history.push(html! {
-                        span.prompt { "λ " } (highlight(&input)) br;
-                        (output)
-                    });
+                    history.push(output);
 
                     input_history.push(input);
                     i = input_history.len();
@@ -17,4 +17,7 @@ extern "C" {
 
     #[wasm_bindgen]
     pub fn set_cursor(el: &HtmlElement, position: usize);
+
+    #[wasm_bindgen]
+    pub fn prompt_line(message: &str) -> Option<String>;
 }
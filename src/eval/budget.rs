@@ -0,0 +1,289 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+use super::ErrorKind;
+
+/// A limit on the resources an evaluation may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// The number of function calls
+    Steps(u64),
+    /// The number of collection elements and string bytes created by builtins
+    Allocations(u64),
+    /// The number of nested function calls
+    Depth(usize),
+    /// The time spent evaluating
+    #[cfg(not(target_arch = "wasm32"))]
+    Timeout(Duration),
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Steps(n) => write!(f, "{n} steps"),
+            Self::Allocations(n) => write!(f, "{n} allocated elements"),
+            Self::Depth(n) => write!(f, "{n} nested calls"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Timeout(d) => write!(f, "{d:?}"),
+        }
+    }
+}
+
+/// The resources used by a context so far, and how much it may use
+#[derive(Debug, Clone)]
+pub(crate) struct Budget {
+    pub(crate) max_steps: Option<u64>,
+    pub(crate) max_allocations: Option<u64>,
+    pub(crate) max_depth: Option<usize>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+    steps: u64,
+    allocations: u64,
+    depth: usize,
+    /// Whether an evaluation started from outside is in progress
+    running: bool,
+    /// Time spent by evaluations that have finished since the last reset
+    #[cfg(not(target_arch = "wasm32"))]
+    elapsed: Duration,
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Option<Instant>,
+}
+
+impl Budget {
+    /// Create a budget without any limits
+    pub(crate) const fn new() -> Self {
+        Self {
+            max_steps: None,
+            max_allocations: None,
+            max_depth: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            timeout: None,
+            cancel: None,
+            steps: 0,
+            allocations: 0,
+            depth: 0,
+            running: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            elapsed: Duration::ZERO,
+            #[cfg(not(target_arch = "wasm32"))]
+            start: None,
+        }
+    }
+
+    /// Check whether any limit has been configured
+    pub(crate) fn is_limited(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.timeout.is_some() {
+            return true;
+        }
+
+        self.max_steps.is_some()
+            || self.max_allocations.is_some()
+            || self.max_depth.is_some()
+            || self.cancel.is_some()
+    }
+
+    /// Start an evaluation from outside, starting the clock for the timeout
+    ///
+    /// Returns `false` if an evaluation is already in progress, such as when a builtin evaluates
+    /// code of its own
+    pub(crate) fn start(&mut self) -> bool {
+        if self.running {
+            return false;
+        }
+
+        self.running = true;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.start = Some(Instant::now());
+        }
+        true
+    }
+
+    /// Finish an evaluation started with [`Budget::start`], adding its time to the time used
+    pub(crate) fn stop(&mut self) {
+        self.running = false;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(start) = self.start.take() {
+            self.elapsed += start.elapsed();
+        }
+    }
+
+    /// Forget the resources used so far
+    pub(crate) fn reset(&mut self) {
+        self.steps = 0;
+        self.allocations = 0;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.elapsed = Duration::ZERO;
+            self.start = None;
+        }
+    }
+
+    /// Count a function call
+    pub(crate) fn step(&mut self) -> Result<(), ErrorKind> {
+        self.steps += 1;
+        if let Some(max) = self.max_steps.filter(|&max| self.steps > max) {
+            return Err(ErrorKind::BudgetExceeded(Limit::Steps(max)));
+        }
+        self.cancelled()?;
+
+        // reading the clock is slow compared to a step, so only do it every so often
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.timeout.is_some() {
+            self.start.get_or_insert_with(Instant::now);
+            if self.steps.is_multiple_of(256) {
+                self.timed_out()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fail if the cancel flag is set
+    pub(crate) fn cancelled(&self) -> Result<(), ErrorKind> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|c| c.load(Ordering::Relaxed))
+        {
+            return Err(ErrorKind::Cancelled);
+        }
+
+        Ok(())
+    }
+
+    /// Fail if evaluations have been running for longer than the timeout since the last reset
+    pub(crate) fn timed_out(&self) -> Result<(), ErrorKind> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.timeout {
+            if self.elapsed + self.start.map_or(Duration::ZERO, |s| s.elapsed()) > timeout {
+                return Err(ErrorKind::BudgetExceeded(Limit::Timeout(timeout)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Count entering a function, failing if calls are nested too deeply
+    pub(crate) fn enter(&mut self) -> Result<(), ErrorKind> {
+        if let Some(max) = self.max_depth.filter(|&max| self.depth >= max) {
+            return Err(ErrorKind::BudgetExceeded(Limit::Depth(max)));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Count leaving a function entered with [`Budget::enter`]
+    pub(crate) fn leave(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    /// Count the elements of a collection about to be created
+    pub(crate) fn alloc(&mut self, n: usize) -> Result<(), ErrorKind> {
+        self.allocations = self.allocations.saturating_add(n as u64);
+        match self.max_allocations {
+            Some(max) if self.allocations > max => {
+                Err(ErrorKind::BudgetExceeded(Limit::Allocations(max)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Budget, Limit};
+    use crate::prelude::{
+        ContextBuilder, EvalError as Error, EvalErrorKind as ErrorKind, Expr, Parser,
+    };
+
+    #[test]
+    fn budget() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new("(fn f [x] (f x)) (f 1) (vector 1 2 3 4)").parse()?;
+        let steps = |res: Result<Expr, Error>| res.map_err(|e| e.kind);
+
+        let mut ctx = ContextBuilder::all().step_limit(100).build();
+        ctx.eval(&ast[0])?;
+        let limit = Err(ErrorKind::BudgetExceeded(Limit::Steps(100)));
+        assert_eq!(steps(ctx.eval(&ast[1])), limit);
+        ctx.reset_budget();
+        assert_eq!(steps(ctx.run(&ctx.compile(&ast[1]))), limit);
+
+        let mut ctx = ContextBuilder::all().allocation_limit(6).build();
+        assert!(ctx.eval(&ast[2]).is_ok());
+        assert_eq!(
+            steps(ctx.eval(&ast[2])),
+            Err(ErrorKind::BudgetExceeded(Limit::Allocations(6)))
+        );
+
+        let deep = Parser::new(
+            "(fn f [x] (+ 1 (f x))) (f 1) (fn g [n] (if (= n 0) 0 (g (- n 1)))) (g 20)",
+        )
+        .parse()?;
+        let mut ctx = ContextBuilder::all().depth_limit(50).build();
+        ctx.eval(&deep[0])?;
+        ctx.eval(&deep[2])?;
+        let limit = Err(ErrorKind::BudgetExceeded(Limit::Depth(50)));
+        assert_eq!(steps(ctx.eval(&deep[1])), limit);
+        assert_eq!(steps(ctx.run(&ctx.compile(&deep[1]))), limit);
+        // the depth is back to zero after an error, so shallower calls still work
+        assert_eq!(ctx.eval(&deep[3])?, Expr::Number(0.));
+        assert_eq!(ctx.run(&ctx.compile(&deep[3]))?, Expr::Number(0.));
+
+        let flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let mut ctx = ContextBuilder::all().cancel_flag(flag.clone()).build();
+        ctx.eval(&ast[0])?;
+        flag.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(steps(ctx.eval(&ast[1])), Err(ErrorKind::Cancelled));
+
+        let timeout = Duration::from_millis(10);
+        let mut ctx = ContextBuilder::all().timeout(timeout).build();
+        ctx.eval(&ast[0])?;
+        assert_eq!(
+            steps(ctx.run(&ctx.compile(&ast[1]))),
+            Err(ErrorKind::BudgetExceeded(Limit::Timeout(timeout)))
+        );
+
+        // only the time spent evaluating counts, not the time between evaluations
+        let timeout = Duration::from_millis(50);
+        let mut ctx = ContextBuilder::all().timeout(timeout).build();
+        ctx.eval(&deep[2])?;
+        std::thread::sleep(timeout);
+        let ast = Parser::new("(g 100)").parse()?;
+        assert_eq!(ctx.eval(&ast[0])?, Expr::Number(0.));
+        Ok(())
+    }
+
+    #[test]
+    fn timeout_adds_up() {
+        let timeout = Duration::from_millis(10);
+        let mut budget = Budget::new();
+        budget.timeout = Some(timeout);
+
+        for _ in 0..2 {
+            assert!(budget.start());
+            std::thread::sleep(timeout / 2 + Duration::from_millis(1));
+            budget.stop();
+        }
+        assert_eq!(
+            budget.timed_out(),
+            Err(ErrorKind::BudgetExceeded(Limit::Timeout(timeout)))
+        );
+        budget.reset();
+        assert_eq!(budget.timed_out(), Ok(()));
+    }
+}
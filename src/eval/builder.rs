@@ -0,0 +1,225 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{atomic::AtomicBool, Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+use super::{budget::Budget, streams::Streams, Context, InputSource, Scope, Sink};
+
+/// A group of builtins that can be enabled in a context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// The special forms, arithmetic, comparisons, collections and assertions
+    ///
+    /// Without it, a context can only evaluate literals and call the builtins of other groups
+    Core,
+    /// String manipulation and parsing, including json
+    Strings,
+    /// Reading from and writing to the standard streams
+    Io,
+    /// Access to the filesystem, when built with the `fs` feature
+    Fs,
+    /// Access to the command line arguments and environment variables
+    Env,
+    /// Spawning other programs
+    Process,
+}
+
+impl Capability {
+    /// Every capability, as enabled by [`Context::new`]
+    pub const ALL: [Self; 6] = [
+        Self::Core,
+        Self::Strings,
+        Self::Io,
+        Self::Fs,
+        Self::Env,
+        Self::Process,
+    ];
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A builder for a context with only some groups of builtins
+///
+/// ```
+/// # use faye::prelude::{Capability, ContextBuilder, Parser};
+/// let mut ctx = ContextBuilder::pure().build();
+///
+/// let ast = Parser::new("(str \"hi \" (+ 1 2)) @cmd-args").parse().unwrap();
+/// assert!(ctx.eval(&ast[0]).is_ok());
+/// assert!(ctx.eval(&ast[1]).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContextBuilder {
+    capabilities: u8,
+    budget: Budget,
+    streams: Streams,
+}
+
+impl ContextBuilder {
+    /// Create a builder with only the [`Capability::Core`] builtins
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            capabilities: Capability::Core.bit(),
+            budget: Budget::new(),
+            streams: Streams::new(),
+        }
+    }
+
+    /// Create a builder for pure computation, which cannot observe or affect the outside world
+    #[must_use]
+    pub const fn pure() -> Self {
+        Self::new().capability(Capability::Strings)
+    }
+
+    /// Create a builder with every capability
+    #[must_use]
+    pub const fn all() -> Self {
+        Self {
+            capabilities: !0,
+            budget: Budget::new(),
+            streams: Streams::new(),
+        }
+    }
+
+    /// Enable a group of builtins
+    #[must_use]
+    pub const fn capability(mut self, capability: Capability) -> Self {
+        self.capabilities |= capability.bit();
+        self
+    }
+
+    /// Disable a group of builtins
+    #[must_use]
+    pub const fn without(mut self, capability: Capability) -> Self {
+        self.capabilities &= !capability.bit();
+        self
+    }
+
+    /// Check whether a group of builtins is enabled
+    #[must_use]
+    pub const fn has(&self, capability: Capability) -> bool {
+        self.capabilities & capability.bit() != 0
+    }
+
+    /// Limit the number of function calls, after which evaluation fails
+    ///
+    /// Calls are counted across evaluations until [`Context::reset_budget`] is called
+    #[must_use]
+    pub const fn step_limit(mut self, steps: u64) -> Self {
+        self.budget.max_steps = Some(steps);
+        self
+    }
+
+    /// Limit the number of collection elements and string bytes that builtins may create
+    #[must_use]
+    pub const fn allocation_limit(mut self, elements: u64) -> Self {
+        self.budget.max_allocations = Some(elements);
+        self
+    }
+
+    /// Limit how deeply function calls may nest, which the step limit alone does not bound
+    #[must_use]
+    pub const fn depth_limit(mut self, depth: usize) -> Self {
+        self.budget.max_depth = Some(depth);
+        self
+    }
+
+    /// Limit the time spent evaluating, after which evaluation fails
+    ///
+    /// Like steps and allocations, the time spent by calls to [`Context::eval`], [`Context::run`]
+    /// and [`Context::call`] adds up until [`Context::reset_budget`] is called. This is not available on wasm, where there is no clock to read
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.budget.timeout = Some(timeout);
+        self
+    }
+
+    /// Stop evaluating when the flag is set, for example from another thread
+    #[must_use]
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.budget.cancel = Some(flag);
+        self
+    }
+
+    /// Take the input of `read-line` and friends from a source instead of the process stdin
+    #[must_use]
+    pub fn stdin(mut self, source: InputSource) -> Self {
+        self.streams.stdin = Some(source);
+        self
+    }
+
+    /// Send the output of `println` to a sink instead of the process stdout
+    #[must_use]
+    pub fn stdout(mut self, sink: Sink) -> Self {
+        self.streams.stdout = Some(sink);
+        self
+    }
+
+    /// Send the output of `eprintln` to a sink instead of the process stderr
+    #[must_use]
+    pub fn stderr(mut self, sink: Sink) -> Self {
+        self.streams.stderr = Some(sink);
+        self
+    }
+
+    /// Build the context
+    #[must_use]
+    pub fn build(self) -> Context {
+        let mut ctx = Context::with_globals(Scope::builtins(&self));
+        if self.budget.is_limited() {
+            ctx.budget = Some(Box::new(self.budget));
+        }
+        ctx.streams = self.streams;
+
+        ctx
+    }
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::{
+        Capability, ContextBuilder, EvalErrorKind as ErrorKind, Expr, Parser, Symbol,
+    };
+
+    #[test]
+    fn capabilities() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new("(+ 1 2) (str 1) (println 1) @cmd-args").parse()?;
+        let sym = Symbol::from;
+
+        let mut ctx = ContextBuilder::new().build();
+        assert_eq!(ctx.eval(&ast[0])?, Expr::Number(3.));
+        assert_eq!(
+            ctx.eval(&ast[1]).map_err(|e| e.kind),
+            Err(ErrorKind::UnknownSymbol(sym("str")))
+        );
+
+        let mut ctx = ContextBuilder::all().without(Capability::Env).build();
+        assert!(ctx.get(&sym("println")).is_some());
+        assert_eq!(
+            ctx.eval(&ast[3]).map_err(|e| e.kind),
+            Err(ErrorKind::UnknownSymbol(sym("@cmd-args")))
+        );
+
+        let mut ctx = ContextBuilder::pure().without(Capability::Core).build();
+        assert_eq!(
+            ctx.eval(&ast[0]).map_err(|e| e.kind),
+            Err(ErrorKind::UnknownSymbol(sym("+")))
+        );
+        assert_eq!(ctx.eval(&ast[1])?, Expr::String("1".into()));
+        Ok(())
+    }
+}
@@ -3,32 +3,119 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{Context, Error, Expr};
+use std::rc::Rc;
+
+use super::{Context, Error, Expr, IntoBuiltin};
 use crate::prelude::{Node, Symbol};
 
-/// type alias for builtin function callbacks
+/// type alias for special form callbacks, which receive their arguments unevaluated
 pub type Callback = fn(&mut Context, &[Node]) -> Result<Expr, Error>;
 
+/// type alias for builtin function callbacks, which receive their arguments evaluated
+pub type FnCallback = Rc<dyn Fn(&mut Context, &[Expr]) -> Result<Expr, Error>>;
+
 /// A builtin function
-#[allow(unpredictable_function_pointer_comparisons)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct BuiltinFn {
     pub(crate) name: Symbol,
-    callback: Callback,
+    pub(crate) callback: Kind,
+    pub(crate) arity: Option<usize>,
+}
+
+/// The calling convention of a builtin function
+#[derive(Clone)]
+pub(crate) enum Kind {
+    Special(Callback),
+    Function(FnCallback),
+}
+
+impl std::fmt::Debug for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Special(_) => write!(f, "Special"),
+            Self::Function(_) => write!(f, "Function"),
+        }
+    }
+}
+
+impl PartialEq for Kind {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Special(a), Self::Special(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Self::Function(a), Self::Function(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
 }
 
 impl BuiltinFn {
-    /// Create a new builtin function
+    /// Create a new special form
     pub fn new<S: Into<String>>(name: S, callback: Callback) -> Self {
         Self {
             name: Symbol::from(name),
-            callback,
+            callback: Kind::Special(callback),
+            arity: None,
+        }
+    }
+
+    /// Create a new builtin function that takes evaluated arguments
+    pub fn function<S, F>(name: S, callback: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&mut Context, &[Expr]) -> Result<Expr, Error> + 'static,
+    {
+        Self {
+            name: Symbol::from(name),
+            callback: Kind::Function(Rc::new(callback)),
+            arity: None,
+        }
+    }
+
+    /// Create a new builtin function from a Rust function with typed arguments
+    pub fn typed<S: Into<String>, Args, F: IntoBuiltin<Args>>(name: S, callback: F) -> Self {
+        Self {
+            name: Symbol::from(name),
+            callback: Kind::Function(callback.into_builtin()),
+            arity: F::ARITY,
         }
     }
 
+    /// Set the number of arguments the builtin takes, as reported by `arity`
+    #[must_use]
+    pub const fn with_arity(mut self, arity: usize) -> Self {
+        self.arity = Some(arity);
+        self
+    }
+
+    /// Check whether the builtin receives its arguments unevaluated
+    #[inline]
+    #[must_use]
+    pub const fn is_special(&self) -> bool {
+        matches!(self.callback, Kind::Special(_))
+    }
+
     /// Evaluate a builtin function with the given arguments
     #[inline]
     pub(crate) fn eval(&self, ctx: &mut Context, args: &[Node]) -> Result<Expr, Error> {
-        (self.callback)(ctx, args)
+        match &self.callback {
+            Kind::Special(f) => f(ctx, args),
+            Kind::Function(f) => {
+                let span = ctx.span.clone();
+                let args = ctx.eval_args(args)?;
+                ctx.span = span;
+                f(ctx, &args)
+            }
+        }
+    }
+
+    /// Call a builtin function with already evaluated arguments
+    #[inline]
+    pub(crate) fn call(&self, ctx: &mut Context, args: &[Expr]) -> Result<Expr, Error> {
+        match &self.callback {
+            Kind::Function(f) => f(ctx, args),
+            Kind::Special(_) => Err(ctx.error(super::ErrorKind::InvalidFunction(Expr::BuiltinFn(
+                self.clone(),
+            )))),
+        }
     }
 }
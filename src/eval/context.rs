@@ -3,25 +3,54 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{Error, ErrorKind, Expr, Scope};
+use std::{collections::BTreeMap, rc::Rc};
+
+use super::{
+    budget::Budget, streams::Streams, BuiltinFn, ContextBuilder, Env, Error, ErrorKind, Expr,
+    InputSource, IntoBuiltin, Scope, Sink, Test,
+};
 use crate::prelude::{Node, NodeKind, Span, Symbol};
+use crate::vm::{Chunk, Compiler, Vm};
 
 /// A context that stores global and local functions
+///
+/// A context is not [`Send`], since the values it stores are reference counted with [`Rc`]. Create
+/// one per thread instead of sharing one
 #[derive(Clone)]
 pub struct Context {
     pub(crate) globals: Scope,
-    pub(crate) locals: Scope,
+    pub(crate) locals: Env,
     pub(crate) span: Span,
+    pub(crate) tests: Vec<Test>,
+    pub(crate) budget: Option<Box<Budget>>,
+    pub(crate) streams: Streams,
+    /// Environment variables set with `setenv`, or removed if `None`, over the process environment
+    pub(crate) vars: BTreeMap<String, Option<String>>,
 }
 
 impl Context {
-    /// Create a new context with the given start and end locations
+    /// Create a new context with every builtin function
     #[must_use]
     pub fn new() -> Self {
+        ContextBuilder::all().build()
+    }
+
+    /// Create a builder for a context with only some groups of builtins
+    #[must_use]
+    pub const fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+
+    /// Create a new context with the given global scope
+    pub(crate) fn with_globals(globals: Scope) -> Self {
         Self {
-            globals: Scope::builtins(),
-            locals: Scope::default(),
+            globals,
+            locals: Env::default(),
             span: Span::default(),
+            tests: Vec::new(),
+            budget: None,
+            streams: Streams::new(),
+            vars: BTreeMap::new(),
         }
     }
 
@@ -31,15 +60,171 @@ impl Context {
         self.locals.get(sym).or_else(|| self.globals.get(sym))
     }
 
+    /// Register a Rust function as a global builtin, converting its arguments and return value
+    ///
+    /// ```
+    /// # use faye::prelude::{Context, Expr, Parser};
+    /// let mut ctx = Context::new();
+    /// ctx.register("repeat", |x: f64, n: usize| vec![x; n]);
+    ///
+    /// let ast = Parser::new("(repeat 1 3)").parse().unwrap();
+    /// assert_eq!(ctx.eval(&ast[0]).unwrap().to_string(), "[1 1 1]");
+    /// ```
+    pub fn register<Args>(&mut self, name: &str, callback: impl IntoBuiltin<Args>) {
+        self.globals.insert(
+            Symbol::from(name),
+            Expr::BuiltinFn(BuiltinFn::typed(name, callback)),
+        );
+    }
+
+    /// Register a global builtin that receives the context and its evaluated arguments as is
+    pub fn register_fn<F>(&mut self, name: &str, callback: F)
+    where
+        F: Fn(&mut Self, &[Expr]) -> Result<Expr, Error> + 'static,
+    {
+        self.globals.register_fn(name, callback);
+    }
+
+    /// Get a function from local or global scope, if the symbol refers to one
+    ///
+    /// Special forms such as `if` are not returned, since they cannot be called with values
+    #[must_use]
+    pub fn get_fn(&self, sym: &Symbol) -> Option<&Expr> {
+        self.get(sym).filter(|v| match v {
+            Expr::BuiltinFn(f) => !f.is_special(),
+            Expr::UserFn(_) | Expr::Closure(_) => true,
+            _ => false,
+        })
+    }
+
+    /// Call a function with already evaluated arguments
+    ///
+    /// ```
+    /// # use faye::prelude::{Context, Expr, Parser, Symbol};
+    /// let mut ctx = Context::new();
+    /// let ast = Parser::new("(fn greet [name] (str \"hi \" name))").parse().unwrap();
+    /// ctx.eval(&ast[0]).unwrap();
+    ///
+    /// let greet = ctx.get_fn(&Symbol::from("greet")).cloned().unwrap();
+    /// let res = ctx.call(&greet, vec![Expr::String("fawn".into())]).unwrap();
+    /// assert_eq!(res, Expr::String("hi fawn".into()));
+    /// ```
+    pub fn call(&mut self, f: &Expr, args: Vec<Expr>) -> Result<Expr, Error> {
+        self.start(|ctx| match f {
+            Expr::BuiltinFn(f) => f.call(ctx, &args),
+            Expr::UserFn(f) => f.call(ctx, &args),
+            Expr::Closure(f) => f.call(ctx, &args),
+            v => Err(ctx.error(ErrorKind::InvalidFunction(v.clone()))),
+        })
+    }
+
     /// List all global functions
     #[must_use]
     pub fn list_globals(&self) -> Vec<Symbol> {
-        let mut globals = self.globals.0.keys().cloned().collect::<Vec<Symbol>>();
+        let mut globals = self
+            .globals
+            .bindings
+            .keys()
+            .cloned()
+            .collect::<Vec<Symbol>>();
         globals.sort();
 
         globals
     }
 
+    /// List all tests defined with `deftest`
+    #[must_use]
+    pub fn tests(&self) -> &[Test] {
+        &self.tests
+    }
+
+    /// Send the output of the program to a sink instead of the process stdout
+    pub fn set_stdout(&mut self, sink: Sink) {
+        self.streams.stdout = Some(sink);
+    }
+
+    /// Send the error stream of the program to a sink instead of the process stderr
+    pub fn set_stderr(&mut self, sink: Sink) {
+        self.streams.stderr = Some(sink);
+    }
+
+    /// Take input for the program from a source instead of the process stdin
+    pub fn set_stdin(&mut self, source: InputSource) {
+        self.streams.stdin = Some(source);
+    }
+
+    /// Turn the result of reading or writing a stream into an evaluation error
+    pub(crate) fn io<T>(&self, res: std::io::Result<T>) -> Result<T, Error> {
+        res.map_err(|e| self.error(ErrorKind::Io(e.to_string())))
+    }
+
+    /// Run an evaluation, timing it against the budget unless it is nested in another one
+    #[inline]
+    fn start<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if !self.budget.as_mut().is_some_and(|b| b.start()) {
+            return f(self);
+        }
+
+        let res = f(self);
+        if let Some(budget) = &mut self.budget {
+            budget.stop();
+        }
+        res
+    }
+
+    /// Forget the steps, allocations and time used so far, keeping the limits
+    pub fn reset_budget(&mut self) {
+        if let Some(budget) = &mut self.budget {
+            budget.reset();
+        }
+    }
+
+    /// Count a function call against the budget
+    #[inline]
+    pub(crate) fn step(&mut self) -> Result<(), Error> {
+        match self.budget.as_mut().map(|b| b.step()) {
+            Some(Err(kind)) => Err(self.error(kind)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail if the evaluation was cancelled or ran out of time, without counting a step
+    pub(crate) fn interrupted(&self) -> Result<(), Error> {
+        match &self.budget {
+            Some(budget) => budget
+                .cancelled()
+                .and_then(|()| budget.timed_out())
+                .map_err(|kind| self.error(kind)),
+            None => Ok(()),
+        }
+    }
+
+    /// Count entering a function against the budget's depth limit
+    #[inline]
+    pub(crate) fn enter(&mut self) -> Result<(), Error> {
+        match self.budget.as_mut().map(|b| b.enter()) {
+            Some(Err(kind)) => Err(self.error(kind)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count leaving a function entered with [`Context::enter`]
+    #[inline]
+    pub(crate) fn leave(&mut self) {
+        if let Some(budget) = &mut self.budget {
+            budget.leave();
+        }
+    }
+
+    /// Count the elements of a collection about to be created against the budget
+    #[inline]
+    pub(crate) fn alloc(&mut self, n: usize) -> Result<(), Error> {
+        match self.budget.as_mut().map(|b| b.alloc(n)) {
+            Some(Err(kind)) => Err(self.error(kind)),
+            _ => Ok(()),
+        }
+    }
+
     /// Create a new evaluation error
     pub(crate) fn error(&self, kind: ErrorKind) -> Error {
         Error::new(kind, self.span.clone())
@@ -52,15 +237,25 @@ impl Context {
 
     /// Evaluate an expression
     pub fn eval(&mut self, ast: &Node) -> Result<Expr, Error> {
+        match &self.budget {
+            Some(_) => self.start(|ctx| ctx.eval_node(ast)),
+            None => self.eval_node(ast),
+        }
+    }
+
+    fn eval_node(&mut self, ast: &Node) -> Result<Expr, Error> {
         match &ast.kind {
             NodeKind::Symbol(sym) => Ok(self
                 .get(sym)
-                .ok_or_else(|| Error::new(ErrorKind::UnknownSymbol(sym.clone()), ast.span.clone()))?
+                .ok_or_else(|| Error::new(ErrorKind::UnknownSymbol(*sym), ast.span.clone()))?
                 .clone()),
             NodeKind::List(list) => match list.split_first() {
                 Some((fun, args)) => {
                     self.span = fun.span.clone();
-                    match self.eval(fun)? {
+                    self.step()?;
+                    let f = self.eval(fun)?;
+                    self.span = fun.span.clone();
+                    match f {
                         Expr::BuiltinFn(f) => f.eval(self, args),
                         Expr::UserFn(f) => f.eval(self, args),
                         Expr::Closure(f) => f.eval(self, args),
@@ -73,11 +268,39 @@ impl Context {
         }
     }
 
+    /// Compile an expression into bytecode for the virtual machine
+    #[must_use]
+    pub fn compile(&self, ast: &Node) -> Rc<Chunk> {
+        let captures = self.locals.symbols();
+        let mut chunk = Compiler::function(self, &[], &captures, ast);
+        chunk.source = Some(Rc::new(ast.clone()));
+        Rc::new(chunk)
+    }
+
+    /// Run a compiled expression, producing the same result as evaluating it
+    ///
+    /// The expression is compiled again if a special form was rebound since, as it may no longer be one
+    pub fn run(&mut self, chunk: &Rc<Chunk>) -> Result<Expr, Error> {
+        let chunk = match &chunk.source {
+            Some(ast) if chunk.generation != self.globals.generation => {
+                let mut fresh = Compiler::function(self, &[], &chunk.captures, ast);
+                fresh.source = Some(ast.clone());
+                Rc::new(fresh)
+            }
+            _ => chunk.clone(),
+        };
+        self.start(|ctx| Vm::run(ctx, chunk))
+    }
+
     /// Evaluate an expression, temporarily replacing the current locals
-    pub(crate) fn eval_scoped(&mut self, ast: &Node, locals: Scope) -> Result<Expr, Error> {
+    ///
+    /// This counts as entering a function, for the budget's depth limit
+    pub(crate) fn eval_scoped(&mut self, ast: &Node, locals: Env) -> Result<Expr, Error> {
+        self.enter()?;
         let locals = std::mem::replace(&mut self.locals, locals);
         let res = self.eval(ast);
         self.locals = locals;
+        self.leave();
         res
     }
 
@@ -105,10 +328,7 @@ impl Context {
     }
 
     /// Get a list of arguments with a specific length
-    pub(crate) fn get_n<'a, const N: usize>(
-        &self,
-        args: &'a [Node],
-    ) -> Result<&'a [Node; N], Error> {
+    pub(crate) fn get_n<'a, T, const N: usize>(&self, args: &'a [T]) -> Result<&'a [T; N], Error> {
         args.try_into().map_err(|_| {
             if args.len() < N {
                 self.error(ErrorKind::MissingArguments)
@@ -120,20 +340,25 @@ impl Context {
 
     /// Compare between list of arguments of the same type using a predicate
     pub(crate) fn compare<T>(
-        &mut self,
-        args: &[Node],
+        &self,
+        args: &[Expr],
         op: impl Fn(&T, &T) -> bool,
     ) -> Result<Expr, Error>
     where
         T: for<'a> TryFrom<&'a Expr> + PartialEq,
     {
-        let args = self
-            .eval_args(args)
-            .and_then(|v| self.downcast_all::<T>(&v))?;
+        // every argument is checked, even after the comparison is known to fail
+        let mut res = true;
+        let mut prev: Option<T> = None;
+        for arg in args {
+            let arg = self.downcast::<T>(arg)?;
+            if let Some(prev) = &prev {
+                res &= op(prev, &arg);
+            }
+            prev = Some(arg);
+        }
 
-        Ok(Expr::Bool(
-            args.iter().zip(args.iter().skip(1)).all(|(a, b)| op(a, b)),
-        ))
+        Ok(Expr::Bool(res))
     }
 }
 
@@ -142,3 +367,105 @@ impl Default for Context {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::prelude::{Context, EvalErrorKind as ErrorKind, Expr, Parser, Symbol};
+
+    #[test]
+    fn register() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(
+            "(scale [1 2] 3) (scale [1] \"x\") (scale [1]) (halve 3) (halve 4) (count) (arity scale)",
+        )
+        .parse()?;
+        let counter = Rc::new(Cell::new(0));
+        let mut ctx = Context::default();
+        ctx.register("scale", |v: Vec<f64>, n: f64| {
+            v.into_iter().map(|x| x * n).collect::<Vec<_>>()
+        });
+        ctx.register("halve", |n: i64| match n % 2 {
+            0 => Ok(n / 2),
+            _ => Err(ErrorKind::InvalidArgument(Expr::Number(n as f64))),
+        });
+        let c = counter.clone();
+        ctx.register("count", move || c.set(c.get() + 1));
+
+        let res = ast
+            .iter()
+            .map(|n| ctx.eval(n).map_err(|e| e.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            res,
+            [
+                Ok(Expr::Vector(
+                    vec![Expr::Number(3.), Expr::Number(6.)].into()
+                )),
+                Err(ErrorKind::InvalidArgument(Expr::String("x".into()))),
+                Err(ErrorKind::MissingArguments),
+                Err(ErrorKind::InvalidArgument(Expr::Number(3.))),
+                Ok(Expr::Number(2.)),
+                Ok(Expr::Nil),
+                Ok(Expr::Number(2.)),
+            ]
+        );
+        assert_eq!(counter.get(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn register_int_range() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(
+            "(int 9223372036854774784) (int 9223372036854775808) (size 18446744073709551616) (size -1)",
+        )
+        .parse()?;
+        let mut ctx = Context::default();
+        ctx.register("int", |n: i64| n);
+        ctx.register("size", |n: usize| n);
+
+        let res = ast
+            .iter()
+            .map(|n| ctx.eval(n).map_err(|e| e.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            res,
+            [
+                Ok(Expr::Number(9_223_372_036_854_774_784.)),
+                Err(ErrorKind::InvalidArgument(Expr::Number(2f64.powi(63)))),
+                Err(ErrorKind::InvalidArgument(Expr::Number(2f64.powi(64)))),
+                Err(ErrorKind::InvalidArgument(Expr::Number(-1.))),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn call() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new("(const on-add (let (n 10) (lambda [x] (+ x n))))").parse()?;
+        let mut ctx = Context::default();
+        ctx.eval(&ast[0])?;
+
+        let sym = Symbol::from;
+        let handler = ctx
+            .get_fn(&sym("on-add"))
+            .cloned()
+            .ok_or("missing handler")?;
+        assert_eq!(
+            ctx.call(&handler, vec![Expr::Number(5.)])?,
+            Expr::Number(15.)
+        );
+        assert_eq!(
+            ctx.call(&handler, vec![]).map_err(|e| e.kind),
+            Err(ErrorKind::MissingArguments)
+        );
+        assert_eq!(
+            ctx.call(&Expr::Number(1.), vec![]).map_err(|e| e.kind),
+            Err(ErrorKind::InvalidFunction(Expr::Number(1.)))
+        );
+        assert!(ctx.get_fn(&sym("if")).is_none());
+        assert!(ctx.get_fn(&sym("+")).is_some());
+        Ok(())
+    }
+}
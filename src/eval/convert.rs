@@ -0,0 +1,288 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{any::Any, rc::Rc};
+
+use super::{builtin::FnCallback, Context, ErrorKind, Expr, Opaque};
+use crate::prelude::Symbol;
+
+/// A type that can be taken as an argument by a registered function
+pub trait FromExpr: Sized {
+    /// Convert an expression, or return `None` if it has the wrong type
+    fn from_expr(expr: &Expr) -> Option<Self>;
+}
+
+/// A type that can be returned to faye from a registered function
+pub trait IntoExpr {
+    /// Convert a value into an expression
+    fn into_expr(self) -> Expr;
+}
+
+/// The return value of a registered function, which may fail
+pub trait IntoReturn {
+    /// Convert a value into an expression or an error
+    fn into_return(self) -> Result<Expr, ErrorKind>;
+}
+
+/// A Rust function that can be registered as a builtin function
+///
+/// This is implemented for closures of up to 6 arguments that implement [`FromExpr`],
+/// returning a value that implements [`IntoReturn`]
+pub trait IntoBuiltin<Args> {
+    /// The number of arguments the function takes, if it is fixed
+    const ARITY: Option<usize> = None;
+
+    /// Wrap the function in a callback that converts its arguments and checks their count
+    fn into_builtin(self) -> FnCallback;
+}
+
+impl FromExpr for Expr {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        Some(expr.clone())
+    }
+}
+
+impl FromExpr for f64 {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from_expr_int {
+    ($($t:ty),*) => {$(
+        impl FromExpr for $t {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+            fn from_expr(expr: &Expr) -> Option<Self> {
+                match expr {
+                    // `MAX + 1` is a power of two, which a float holds exactly, unlike `MAX` itself
+                    Expr::Number(n) if n.fract() == 0. && *n >= Self::MIN as f64 && *n < Self::MAX as f64 + 1. => {
+                        Some(*n as Self)
+                    }
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_expr_int!(i64, usize);
+
+impl FromExpr for bool {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromExpr for char {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Char(c) => Some(*c),
+            _ => None,
+        }
+    }
+}
+
+impl FromExpr for String {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::String(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl FromExpr for Rc<str> {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromExpr for Symbol {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Symbol(s) => Some(*s),
+            _ => None,
+        }
+    }
+}
+
+impl FromExpr for Opaque {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Opaque(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Any> FromExpr for Rc<T> {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Opaque(v) => v.downcast(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromExpr> FromExpr for Vec<T> {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::List(v) | Expr::Vector(v) => v.iter().map(T::from_expr).collect(),
+            Expr::Nil => Some(Self::new()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromExpr> FromExpr for Option<T> {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Nil => Some(None),
+            _ => T::from_expr(expr).map(Some),
+        }
+    }
+}
+
+impl IntoExpr for Expr {
+    fn into_expr(self) -> Expr {
+        self
+    }
+}
+
+impl IntoExpr for () {
+    fn into_expr(self) -> Expr {
+        Expr::Nil
+    }
+}
+
+impl IntoExpr for f64 {
+    fn into_expr(self) -> Expr {
+        Expr::Number(self)
+    }
+}
+
+macro_rules! impl_into_expr_int {
+    ($($t:ty),*) => {$(
+        impl IntoExpr for $t {
+            #[allow(clippy::cast_precision_loss)]
+            fn into_expr(self) -> Expr {
+                Expr::Number(self as f64)
+            }
+        }
+    )*};
+}
+
+impl_into_expr_int!(i64, usize);
+
+impl IntoExpr for bool {
+    fn into_expr(self) -> Expr {
+        Expr::Bool(self)
+    }
+}
+
+impl IntoExpr for char {
+    fn into_expr(self) -> Expr {
+        Expr::Char(self)
+    }
+}
+
+impl IntoExpr for String {
+    fn into_expr(self) -> Expr {
+        Expr::String(self.into())
+    }
+}
+
+impl IntoExpr for &str {
+    fn into_expr(self) -> Expr {
+        Expr::String(self.into())
+    }
+}
+
+impl IntoExpr for Rc<str> {
+    fn into_expr(self) -> Expr {
+        Expr::String(self)
+    }
+}
+
+impl IntoExpr for Symbol {
+    fn into_expr(self) -> Expr {
+        Expr::Symbol(self)
+    }
+}
+
+impl IntoExpr for Opaque {
+    fn into_expr(self) -> Expr {
+        Expr::Opaque(self)
+    }
+}
+
+impl<T: IntoExpr> IntoExpr for Vec<T> {
+    fn into_expr(self) -> Expr {
+        Expr::Vector(self.into_iter().map(IntoExpr::into_expr).collect())
+    }
+}
+
+impl<T: IntoExpr> IntoExpr for Option<T> {
+    fn into_expr(self) -> Expr {
+        self.map_or(Expr::Nil, IntoExpr::into_expr)
+    }
+}
+
+impl<T: IntoExpr> IntoReturn for T {
+    fn into_return(self) -> Result<Expr, ErrorKind> {
+        Ok(self.into_expr())
+    }
+}
+
+impl<T: IntoExpr> IntoReturn for Result<T, ErrorKind> {
+    fn into_return(self) -> Result<Expr, ErrorKind> {
+        self.map(IntoExpr::into_expr)
+    }
+}
+
+/// Convert an argument, pointing at the call site if it has the wrong type
+fn arg<T: FromExpr>(ctx: &Context, expr: &Expr) -> Result<T, super::Error> {
+    T::from_expr(expr).ok_or_else(|| ctx.error(ErrorKind::InvalidArgument(expr.clone())))
+}
+
+macro_rules! impl_into_builtin {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoBuiltin<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoReturn,
+            $($arg: FromExpr,)*
+        {
+            const ARITY: Option<usize> = Some(<[&str]>::len(&[$(stringify!($arg)),*]));
+
+            #[allow(non_snake_case, unused_variables)]
+            fn into_builtin(self) -> FnCallback {
+                Rc::new(move |ctx: &mut Context, args: &[Expr]| {
+                    let [$($arg),*] = ctx.get_n(args)?;
+                    self($(arg::<$arg>(ctx, $arg)?),*)
+                        .into_return()
+                        .map_err(|kind| ctx.error(kind))
+                })
+            }
+        }
+    };
+}
+
+impl_into_builtin!();
+impl_into_builtin!(A);
+impl_into_builtin!(A, B);
+impl_into_builtin!(A, B, C);
+impl_into_builtin!(A, B, C, D);
+impl_into_builtin!(A, B, C, D, E);
+impl_into_builtin!(A, B, C, D, E, G);
@@ -0,0 +1,66 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::rc::Rc;
+
+use super::Expr;
+use crate::prelude::Symbol;
+
+/// A persistent environment of local variables
+///
+/// Environments are linked lists of frames with shared parents,
+/// so extending or capturing one never copies the bindings it already has
+#[derive(Debug, Default, Clone)]
+pub struct Env(Option<Rc<Frame>>);
+
+/// A set of bindings introduced together, such as by a `let` or a function call
+#[derive(Debug)]
+struct Frame {
+    bindings: Vec<(Symbol, Expr)>,
+    parent: Env,
+}
+
+impl Env {
+    /// Create a new environment with the given bindings on top of this one
+    #[must_use]
+    pub fn extend(&self, bindings: Vec<(Symbol, Expr)>) -> Self {
+        if bindings.is_empty() {
+            return self.clone();
+        }
+
+        Self(Some(Rc::new(Frame {
+            bindings,
+            parent: self.clone(),
+        })))
+    }
+
+    /// Get the value of a variable, looking through the innermost frames first
+    pub(crate) fn get(&self, sym: &Symbol) -> Option<&Expr> {
+        let mut env = self;
+        while let Some(frame) = &env.0 {
+            // later bindings in the same frame shadow earlier ones
+            if let Some((_, v)) = frame.bindings.iter().rev().find(|(s, _)| s == sym) {
+                return Some(v);
+            }
+            env = &frame.parent;
+        }
+
+        None
+    }
+
+    /// List the names of every variable in the environment, sorted and without duplicates
+    pub(crate) fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+        let mut env = self;
+        while let Some(frame) = &env.0 {
+            symbols.extend(frame.bindings.iter().map(|(s, _)| *s));
+            env = &frame.parent;
+        }
+
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+}
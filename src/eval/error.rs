@@ -3,8 +3,8 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use super::Expr;
-use crate::prelude::{Span, Symbol};
+use super::{Expr, Limit};
+use crate::prelude::{ParserError, Span, Symbol};
 
 /// An evaluation error with a start and end location
 #[derive(Debug, PartialEq, Clone)]
@@ -37,6 +37,16 @@ pub enum ErrorKind {
     TooManyArguments,
     InvalidFunction(Expr),
     InvalidArgument(Expr),
+    AssertionFailed(Expr),
+    AssertionNotEqual(Expr, Expr),
+    InvalidJson(String, usize, usize),
+    InvalidFormat(String),
+    InvalidRegex(String),
+    InvalidSyntax(ParserError),
+    BudgetExceeded(Limit),
+    Cancelled,
+    OutOfMemory(usize),
+    Io(String),
 }
 
 impl std::fmt::Display for ErrorKind {
@@ -49,6 +59,20 @@ impl std::fmt::Display for ErrorKind {
             Self::InvalidArgument(v) => {
                 write!(f, "`{v}` is not a valid argument for this function")
             }
+            Self::AssertionFailed(v) => write!(f, "Assertion `{v}` failed"),
+            Self::AssertionNotEqual(expected, actual) => {
+                write!(f, "Expected `{expected}` but found `{actual}`")
+            }
+            Self::InvalidJson(msg, line, col) => {
+                write!(f, "Invalid JSON at line {line}, column {col}: {msg}")
+            }
+            Self::InvalidFormat(msg) => write!(f, "Invalid format string: {msg}"),
+            Self::InvalidRegex(msg) => write!(f, "Invalid regular expression: {msg}"),
+            Self::InvalidSyntax(e) => write!(f, "Invalid syntax: {e}"),
+            Self::BudgetExceeded(limit) => write!(f, "Evaluation exceeded its limit of {limit}"),
+            Self::Cancelled => write!(f, "Evaluation was cancelled"),
+            Self::OutOfMemory(n) => write!(f, "Could not allocate {n} bytes"),
+            Self::Io(msg) => write!(f, "I/O error: {msg}"),
         }
     }
 }
@@ -3,35 +3,51 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{BuiltinFn, Closure, UserFn};
-use crate::prelude::{Node, NodeKind, Symbol};
+use std::rc::Rc;
+
+use super::{BuiltinFn, Closure, Opaque, Record, UserFn};
+use crate::{
+    lexer::escape,
+    prelude::{Node, NodeKind, Span, Symbol},
+};
 
 /// The result of an evaluated expression
+///
+/// Strings and collections are reference counted, so cloning a value never copies its contents.
+/// Nested collections share their elements, so a cons-style list built with `(list x tail)` shares
+/// `tail` instead of copying it, but the slices are not persistent: building a collection one
+/// element at a time still copies it at each step
+///
+/// Values use [`Rc`], so they are neither [`Send`] nor [`Sync`] and cannot move between threads
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     Number(f64),
     Bool(bool),
-    String(String),
-    Display(String),
+    String(Rc<str>),
     Char(char),
     Symbol(Symbol),
     Keyword(String),
-    List(Vec<Expr>),
-    Vector(Vec<Expr>),
+    List(Rc<[Expr]>),
+    Vector(Rc<[Expr]>),
     BuiltinFn(BuiltinFn),
     UserFn(UserFn),
     Closure(Closure),
+    Opaque(Opaque),
+    Record(Record),
     Nil,
 }
 
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Number(n) if n.is_nan() => write!(f, "##NaN"),
+            Self::Number(n) if n.is_infinite() => {
+                write!(f, "{}", if *n > 0. { "##Inf" } else { "##-Inf" })
+            }
             Self::Number(n) => write!(f, "{n}"),
             Self::Bool(b) => write!(f, "{b}"),
-            Self::String(s) => write!(f, "\"{}\"", s.replace('"', "\\\"")),
-            Self::Display(s) => write!(f, "{s}"),
-            Self::Char(c) => write!(f, "'{c}'"),
+            Self::String(s) => write!(f, "\"{}\"", escape(s, '"')),
+            Self::Char(c) => write!(f, "'{}'", escape(&c.to_string(), '\'')),
             Self::Symbol(s) => write!(f, "{s}"),
             Self::Keyword(s) => write!(f, ":{s}"),
             Self::List(v) => write!(
@@ -54,6 +70,8 @@ impl std::fmt::Display for Expr {
             Self::BuiltinFn(v) => write!(f, "{}", v.name),
             Self::UserFn(v) => write!(f, "{}", v.name),
             Self::Closure(_) => write!(f, "#<lambda>"),
+            Self::Opaque(v) => write!(f, "{v}"),
+            Self::Record(r) => write!(f, "{r}"),
         }
     }
 }
@@ -63,22 +81,56 @@ impl From<&Node> for Expr {
         match &node.kind {
             NodeKind::Number(n) => Self::Number(*n),
             NodeKind::Bool(b) => Self::Bool(*b),
-            NodeKind::String(s) => Self::String(s.clone()),
+            NodeKind::String(s) => Self::String(s.as_str().into()),
+            #[cfg(feature = "regex")]
+            NodeKind::Regex(r) => super::regex::literal(r),
+            #[cfg(not(feature = "regex"))]
+            NodeKind::Regex(r) => Self::String(r.as_str().into()),
             NodeKind::Char(c) => Self::Char(*c),
-            NodeKind::Symbol(s) => Self::Symbol(s.clone()),
-            NodeKind::Keyword(Symbol(s)) => Self::Keyword(s.clone()),
+            NodeKind::Symbol(s) => Self::Symbol(*s),
+            NodeKind::Keyword(s) => Self::Keyword(s.to_string()),
             NodeKind::List(l) if l.is_empty() => Self::Nil,
             NodeKind::List(l) => Self::List(l.iter().map(From::from).collect()),
             NodeKind::Vector(v) => Self::Vector(v.iter().map(From::from).collect()),
             NodeKind::Nil => Self::Nil,
+            NodeKind::Value(v) => v.clone(),
         }
     }
 }
 
+impl Expr {
+    /// Turn a value back into code, giving every node the same span
+    ///
+    /// Values with no written form, such as functions and records, are kept as they are, so
+    /// the code does not depend on what their names refer to when it runs
+    #[must_use]
+    pub fn to_node(&self, span: &Span) -> Node {
+        let nodes = |v: &[Self]| v.iter().map(|e| e.to_node(span)).collect();
+        let kind = match self {
+            Self::Number(n) => NodeKind::Number(*n),
+            Self::Bool(b) => NodeKind::Bool(*b),
+            Self::String(s) => NodeKind::String(s.to_string()),
+            Self::Char(c) => NodeKind::Char(*c),
+            Self::Symbol(s) => NodeKind::Symbol(*s),
+            Self::Keyword(k) => NodeKind::Keyword(Symbol::from(k.as_str())),
+            Self::List(v) => NodeKind::List(nodes(v)),
+            Self::Vector(v) => NodeKind::Vector(nodes(v)),
+            Self::Nil => NodeKind::Nil,
+            Self::BuiltinFn(_)
+            | Self::UserFn(_)
+            | Self::Closure(_)
+            | Self::Opaque(_)
+            | Self::Record(_) => NodeKind::Value(self.clone()),
+        };
+
+        Node::new(kind, span.clone())
+    }
+}
+
 impl From<&Expr> for String {
     fn from(v: &Expr) -> Self {
         match v {
-            Expr::String(s) => s.clone(),
+            Expr::String(s) => s.to_string(),
             Expr::Char(c) => c.to_string(),
             _ => v.to_string(),
         }
@@ -0,0 +1,533 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Range;
+
+use super::{Context, Error, ErrorKind, Expr, Scope};
+use crate::prelude::{Node, NodeKind, Span, Symbol};
+
+/// Register the `format` special form
+///
+/// It receives its arguments unevaluated, so errors can point inside the format string and `{name}`
+/// placeholders can read local variables, which the vm does not pass to builtin functions. Like
+/// other special forms it is not a value that `apply` or higher-order functions can call, so wrap
+/// it in a `lambda` to pass it around
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register("format", format);
+}
+
+/// Where the value of a placeholder comes from
+#[derive(Debug, PartialEq)]
+enum Arg {
+    /// The positional argument after the previous one, as in `{}`
+    Next,
+    /// A positional argument, as in `{0}`
+    Index(usize),
+    /// A keyword argument or a variable in scope, as in `{name}`
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// How a value is rendered, as in `{:x}`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Display,
+    Debug,
+    LowerHex,
+    UpperHex,
+    Octal,
+    Binary,
+    Exp,
+}
+
+/// The part of a placeholder after the `:`
+#[derive(Debug, PartialEq)]
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    sign: bool,
+    alternate: bool,
+    zero: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+    ty: Type,
+}
+
+#[derive(Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    /// A placeholder and the chars of the format string it spans
+    Placeholder(Arg, Spec, Range<usize>),
+}
+
+/// A message about a format string, and the chars it refers to
+type FormatError = (String, Range<usize>);
+
+fn format(ctx: &mut Context, args: &[Node]) -> Result<Expr, Error> {
+    let (template, rest) = args
+        .split_first()
+        .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+    let string = match ctx.eval(template)? {
+        Expr::String(s) => s,
+        e => return Err(ctx.error(ErrorKind::InvalidArgument(e))),
+    };
+    let fail = |(msg, range): FormatError| {
+        Error::new(ErrorKind::InvalidFormat(msg), literal_span(template, range))
+    };
+
+    let pieces = parse(&string).map_err(fail)?;
+
+    // positional arguments come first, then keyword arguments as `:name value` pairs
+    let mut next = 0;
+    let positional = pieces
+        .iter()
+        .filter_map(|p| match p {
+            Piece::Placeholder(Arg::Next, ..) => {
+                next += 1;
+                Some(next)
+            }
+            Piece::Placeholder(Arg::Index(i), ..) => Some(i + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+        .min(rest.len());
+    let (positional, named) = rest.split_at(positional);
+    let positional = ctx.eval_args(positional)?;
+    if named.len() % 2 != 0 {
+        return Err(ctx.error(ErrorKind::TooManyArguments));
+    }
+    let mut keywords = Vec::with_capacity(named.len() / 2);
+    for pair in named.chunks(2) {
+        let NodeKind::Keyword(name) = &pair[0].kind else {
+            return Err(ctx.error(ErrorKind::TooManyArguments));
+        };
+        keywords.push((*name, ctx.eval(&pair[1])?));
+    }
+
+    let mut out = String::new();
+    let mut next = 0;
+    for piece in pieces {
+        let (arg, spec, range) = match piece {
+            Piece::Text(text) => {
+                ctx.alloc(text.len())?;
+                out.push_str(&text);
+                continue;
+            }
+            Piece::Placeholder(arg, spec, range) => (arg, spec, range),
+        };
+
+        // the padding is charged before it is built, and whatever else the value takes after
+        let width = spec.width.unwrap_or(0);
+        ctx.alloc(width)?;
+
+        let missing = || "there is no argument for this placeholder".to_owned();
+        let value = match arg {
+            Arg::Next => {
+                next += 1;
+                positional
+                    .get(next - 1)
+                    .ok_or_else(|| (missing(), range.clone()))
+            }
+            Arg::Index(i) => positional.get(i).ok_or_else(|| (missing(), range.clone())),
+            Arg::Name(name) => {
+                // names that were never interned cannot be bound, so they are not interned here
+                Symbol::lookup(&name)
+                    .and_then(|sym| {
+                        keywords
+                            .iter()
+                            .find(|(k, _)| *k == sym)
+                            .map(|(_, v)| v)
+                            .or_else(|| ctx.get(&sym))
+                    })
+                    .ok_or_else(|| {
+                        (
+                            format!("`{name}` is not an argument or in scope"),
+                            range.clone(),
+                        )
+                    })
+            }
+        }
+        .map_err(fail)?;
+
+        let rendered = render(value, &spec)
+            .ok_or_else(|| ctx.error(ErrorKind::InvalidArgument(value.clone())))?;
+        ctx.alloc(rendered.len().saturating_sub(width))?;
+        out.push_str(&rendered);
+    }
+
+    Ok(Expr::String(out.into()))
+}
+
+/// Split a format string into text and placeholders
+fn parse(string: &str) -> Result<Vec<Piece>, FormatError> {
+    let chars = string.chars().collect::<Vec<_>>();
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                text.push(chars[i]);
+                i += 2;
+            }
+            ('}', _) => return Err(("unmatched `}`".to_owned(), i..i + 1)),
+            ('{', _) => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|&c| c == '}')
+                    .map(|n| i + n)
+                    .ok_or_else(|| ("unclosed placeholder".to_owned(), i..chars.len()))?;
+
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+
+                let colon = chars[i + 1..end]
+                    .iter()
+                    .position(|&c| c == ':')
+                    .map_or(end, |n| i + 1 + n);
+                let arg = parse_arg(&chars, i + 1..colon)?;
+                let spec = parse_spec(&chars, (colon + 1).min(end)..end)?;
+
+                pieces.push(Piece::Placeholder(arg, spec, i..end + 1));
+                i = end + 1;
+            }
+            (c, _) => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// Parse the part of a placeholder before the `:`
+fn parse_arg(chars: &[char], range: Range<usize>) -> Result<Arg, FormatError> {
+    let arg = chars[range.clone()].iter().collect::<String>();
+    let arg = arg.trim();
+
+    if arg.is_empty() {
+        Ok(Arg::Next)
+    } else if arg.chars().all(|c| c.is_ascii_digit()) {
+        arg.parse()
+            .map(Arg::Index)
+            .map_err(|_| (format!("`{arg}` is not a valid argument index"), range))
+    } else if arg.chars().any(|c| c.is_whitespace() || c == '{') {
+        Err((format!("`{arg}` is not a valid argument name"), range))
+    } else {
+        Ok(Arg::Name(arg.to_owned()))
+    }
+}
+
+/// Parse the part of a placeholder after the `:`, as in `[[fill]align][+][#][0][width][.precision][type]`
+fn parse_spec(chars: &[char], range: Range<usize>) -> Result<Spec, FormatError> {
+    let s = &chars[range.clone()];
+    let mut spec = Spec {
+        fill: ' ',
+        align: None,
+        sign: false,
+        alternate: false,
+        zero: false,
+        width: None,
+        precision: None,
+        ty: Type::Display,
+    };
+    let align = |c: Option<&char>| match c {
+        Some('<') => Some(Align::Left),
+        Some('^') => Some(Align::Center),
+        Some('>') => Some(Align::Right),
+        _ => None,
+    };
+
+    let mut i = 0;
+    if let Some(a) = align(s.get(1)) {
+        spec.fill = s[0];
+        spec.align = Some(a);
+        i = 2;
+    } else if let Some(a) = align(s.first()) {
+        spec.align = Some(a);
+        i = 1;
+    }
+    let mut flag = |c| {
+        let found = s.get(i) == Some(&c);
+        i += usize::from(found);
+        found
+    };
+    spec.sign = flag('+');
+    spec.alternate = flag('#');
+    spec.zero = flag('0');
+
+    // like in Rust, widths and precisions are capped, so a placeholder cannot ask for gigabytes
+    let number = |i: &mut usize| {
+        let start = *i;
+        while s.get(*i).is_some_and(char::is_ascii_digit) {
+            *i += 1;
+        }
+        if start == *i {
+            return Ok(None);
+        }
+
+        let digits = s[start..*i].iter().collect::<String>();
+        match digits.parse::<u16>() {
+            Ok(n) => Ok(Some(usize::from(n))),
+            Err(_) => Err((
+                format!("`{digits}` is larger than {}", u16::MAX),
+                range.start + start..range.start + *i,
+            )),
+        }
+    };
+    spec.width = number(&mut i)?;
+    if s.get(i) == Some(&'.') {
+        i += 1;
+        spec.precision = Some(number(&mut i)?.ok_or_else(|| {
+            let at = range.start + i;
+            ("expected a precision after `.`".to_owned(), at - 1..at)
+        })?);
+    }
+
+    let ty = s[i..].iter().collect::<String>();
+    spec.ty = match ty.as_str() {
+        "" => Type::Display,
+        "?" => Type::Debug,
+        "x" => Type::LowerHex,
+        "X" => Type::UpperHex,
+        "o" => Type::Octal,
+        "b" => Type::Binary,
+        "e" => Type::Exp,
+        _ => return Err((format!("unknown format `{ty}`"), range.start + i..range.end)),
+    };
+
+    Ok(spec)
+}
+
+/// Render a value, or return `None` if it cannot be rendered with the spec
+fn render(value: &Expr, spec: &Spec) -> Option<String> {
+    let (sign, prefix, body) = match (value, spec.ty) {
+        (_, Type::Debug) => ("", "", value.to_string()),
+        (Expr::Number(n), Type::Display | Type::Exp) => {
+            let body = match (spec.ty, spec.precision) {
+                (Type::Exp, Some(p)) => format!("{:.p$e}", n.abs()),
+                (Type::Exp, None) => format!("{:e}", n.abs()),
+                (_, Some(p)) => format!("{:.p$}", n.abs()),
+                _ => Expr::Number(n.abs()).to_string(),
+            };
+            (sign(*n, spec), "", body)
+        }
+        (Expr::Number(n), _) if n.fract() == 0. && n.abs() < 2f64.powi(63) => {
+            #[allow(clippy::cast_possible_truncation)]
+            let int = n.abs() as u64;
+            let (prefix, body) = match spec.ty {
+                Type::LowerHex => ("0x", format!("{int:x}")),
+                Type::UpperHex => ("0x", format!("{int:X}")),
+                Type::Octal => ("0o", format!("{int:o}")),
+                _ => ("0b", format!("{int:b}")),
+            };
+            (
+                sign(*n, spec),
+                if spec.alternate { prefix } else { "" },
+                body,
+            )
+        }
+        (_, Type::Display) => {
+            let body = match value {
+                Expr::String(s) => s.to_string(),
+                Expr::Char(c) => c.to_string(),
+                _ => value.to_string(),
+            };
+            let body = match spec.precision {
+                Some(p) => body.chars().take(p).collect(),
+                None => body,
+            };
+            ("", "", body)
+        }
+        _ => return None,
+    };
+
+    let len = sign.chars().count() + prefix.len() + body.chars().count();
+    let padding = spec.width.unwrap_or(0).saturating_sub(len);
+    let numeric = matches!(value, Expr::Number(_)) && spec.ty != Type::Debug;
+
+    if spec.zero && numeric {
+        return Some(format!("{sign}{prefix}{}{body}", "0".repeat(padding)));
+    }
+
+    let default = if numeric { Align::Right } else { Align::Left };
+    let (before, after) = match spec.align.unwrap_or(default) {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0),
+    };
+    let fill = |n| spec.fill.to_string().repeat(n);
+
+    Some(format!(
+        "{}{sign}{prefix}{body}{}",
+        fill(before),
+        fill(after)
+    ))
+}
+
+/// Get the sign to put before a number
+fn sign(n: f64, spec: &Spec) -> &'static str {
+    if n.is_sign_negative() && n != 0. {
+        "-"
+    } else if spec.sign {
+        "+"
+    } else {
+        ""
+    }
+}
+
+/// Get the span of some chars of a format string, if it was written as a literal
+///
+/// Escape sequences take up more of the source than the chars they produce, so they are
+/// skipped over. Format strings computed at runtime point at the expression instead
+fn literal_span(template: &Node, chars: Range<usize>) -> Span {
+    let span = &template.span;
+    if !matches!(template.kind, NodeKind::String(_)) {
+        return span.clone();
+    }
+
+    // the byte offset of each char of the string in the source, and of the closing quote
+    let raw = &span.source.contents()[span.bytes.clone()];
+    let (quote, escapes) = match raw.strip_prefix('r') {
+        // raw strings have no escapes, and start after their hashes
+        Some(rest) => (raw.len() - rest.trim_start_matches('#').len() + 1, false),
+        None => (1, true),
+    };
+    let mut offsets = Vec::new();
+    let mut iter = raw[quote..].char_indices();
+    while let Some((i, c)) = iter.next() {
+        offsets.push(span.bytes.start + quote + i);
+        if escapes && c == '\\' {
+            match iter.next() {
+                Some((_, 'x')) => _ = iter.nth(1),
+                Some((_, 'u')) => _ = iter.find(|&(_, c)| c == '}'),
+                _ => {}
+            }
+        }
+    }
+
+    let offset = |i: usize| offsets.get(i).or(offsets.last()).copied();
+    match (offset(chars.start), offset(chars.end)) {
+        (Some(start), Some(end)) => Span::new(start..end, span.source.clone()),
+        _ => span.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::eval::{ContextBuilder, Limit};
+    use crate::prelude::{Context, EvalErrorKind, Expr, Parser};
+
+    eval_test!(positional: r#"(format "{} items at {:.2}, {{ok}}" 3 4.5)"#, str Ok(
+        "3 items at 4.50, {ok}"
+    ));
+
+    eval_test!(indexed: r#"(format "{1}{0}{1}" "a" "b")"#, str Ok("bab"));
+
+    eval_test!(named: r#"(format "{greeting}, {name}!" :name "faye" :greeting "hi")"#, str Ok(
+        "hi, faye!"
+    ));
+
+    eval_test!(scope: r#"(let (name "faye") (format "hi {name:?}" ))"#, str Ok(r#"hi "faye""#));
+
+    eval_test!(align: r#"(format "[{:>5}|{:*^6}|{:<4}]" "ab" 'c' 1)"#, str Ok("[   ab|**c***|1   ]"));
+
+    eval_test!(numbers: r#"(format "{:05} {:+} {:08.3} {:.1e}" -42 7 -3.14159 1234.5)"#, str Ok(
+        "-0042 +7 -003.142 1.2e3"
+    ));
+
+    eval_test!(radix: r#"(format "{:x} {:#X} {:#010b} {:o}" 255 255 5 -8)"#, str Ok(
+        "ff 0xFF 0b00000101 -10"
+    ));
+
+    eval_test!(error_radix_fraction: r#"(format "{:x}" 1.5)"#, str Err(
+        EvalErrorKind::InvalidArgument(Expr::Number(1.5))
+    ));
+
+    #[test]
+    fn width_budget() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(r#"(format "{:60000}" 1)"#).parse()?;
+        let mut ctx = ContextBuilder::pure().allocation_limit(1000).build();
+
+        assert_eq!(
+            ctx.eval(&ast[0]).map_err(|e| e.kind),
+            Err(EvalErrorKind::BudgetExceeded(Limit::Allocations(1000)))
+        );
+        Ok(())
+    }
+
+    eval_test!(error_extra: r#"(format "{}" 1 2)"#, str Err(EvalErrorKind::TooManyArguments));
+
+    eval_test!(apply_lambda: r#"(apply (lambda [a b] (format "{} {}" a b)) [1 2])"#, str Ok("1 2"));
+
+    #[test]
+    fn error_apply() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(r#"(apply format ["{} {}" 1 2])"#).parse()?;
+
+        assert!(matches!(
+            Context::default().eval(&ast[0]).map_err(|e| e.kind),
+            Err(EvalErrorKind::InvalidFunction(Expr::BuiltinFn(_)))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn error_column() -> Result<(), Box<dyn std::error::Error>> {
+        let cases = [
+            (
+                r#"(format "{:300000000}" 1)"#,
+                "`300000000` is larger than 65535",
+                11..20,
+            ),
+            (
+                r#"(format "{:.70000}" 1)"#,
+                "`70000` is larger than 65535",
+                12..17,
+            ),
+            (
+                r#"(format "ab\"{:.x}" 1)"#,
+                "expected a precision after `.`",
+                15..16,
+            ),
+            (r#"(format "{} {:q}" 1 2)"#, "unknown format `q`", 14..15),
+            (
+                r#"(format "{0} {1}" 1)"#,
+                "there is no argument for this placeholder",
+                13..16,
+            ),
+            (r#"(format "} {")"#, "unmatched `}`", 9..10),
+            (r#"(format "\u{1F98B}\x41 }")"#, "unmatched `}`", 23..24),
+            (r###"(format r#"\n }"#)"###, "unmatched `}`", 14..15),
+            (
+                r#"(format "{nope}")"#,
+                "`nope` is not an argument or in scope",
+                9..15,
+            ),
+        ];
+
+        for (code, msg, bytes) in cases {
+            let ast = Parser::new(code).parse()?;
+            let err = Context::default().eval(&ast[0]).unwrap_err();
+
+            assert_eq!(err.kind, EvalErrorKind::InvalidFormat(msg.to_owned()));
+            assert_eq!(err.span.bytes, bytes, "{code}");
+        }
+        Ok(())
+    }
+}
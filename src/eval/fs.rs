@@ -0,0 +1,184 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{io::Write, path::Path};
+
+use super::{Context, Error, ErrorKind, Expr, Scope};
+
+/// Register the filesystem and `path/` builtins
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register_fn("slurp", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        let path = as_path(ctx, path)?;
+        let string = std::fs::read_to_string(path).map_err(|e| io_error(ctx, path, &e))?;
+        ctx.alloc(string.len())?;
+
+        Ok(Expr::String(string.into()))
+    });
+    scope.register_fn("spit", |ctx, args| {
+        let [path, contents] = ctx.get_n(args)?;
+        let path = as_path(ctx, path)?;
+        std::fs::write(path, String::from(contents)).map_err(|e| io_error(ctx, path, &e))?;
+
+        Ok(Expr::Nil)
+    });
+    scope.register_fn("append-file", |ctx, args| {
+        let [path, contents] = ctx.get_n(args)?;
+        let path = as_path(ctx, path)?;
+        std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|mut f| f.write_all(String::from(contents).as_bytes()))
+            .map_err(|e| io_error(ctx, path, &e))?;
+
+        Ok(Expr::Nil)
+    });
+    scope.register_fn("read-lines", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        let path = as_path(ctx, path)?;
+        let string = std::fs::read_to_string(path).map_err(|e| io_error(ctx, path, &e))?;
+        ctx.alloc(string.len())?;
+
+        Ok(Expr::Vector(
+            string.lines().map(|l| Expr::String(l.into())).collect(),
+        ))
+    });
+    scope.register_fn("file-exists?", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        Ok(Expr::Bool(as_path(ctx, path)?.exists()))
+    });
+    scope.register_fn("list-dir", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        let path = as_path(ctx, path)?;
+        let mut names = std::fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|e| io_error(ctx, path, &e))?;
+        names.sort();
+        ctx.alloc(names.len())?;
+
+        Ok(Expr::Vector(
+            names.into_iter().map(|n| Expr::String(n.into())).collect(),
+        ))
+    });
+    scope.register_fn("mkdir", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        let path = as_path(ctx, path)?;
+        std::fs::create_dir_all(path).map_err(|e| io_error(ctx, path, &e))?;
+
+        Ok(Expr::Nil)
+    });
+    scope.register_fn("delete-file", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        let path = as_path(ctx, path)?;
+        let res = if path.is_dir() {
+            std::fs::remove_dir(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+        res.map_err(|e| io_error(ctx, path, &e))?;
+
+        Ok(Expr::Nil)
+    });
+
+    scope.register_fn("path/join", |ctx, args| {
+        let (first, rest) = args
+            .split_first()
+            .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+        let mut path = as_path(ctx, first)?.to_path_buf();
+        for part in rest {
+            path.push(as_path(ctx, part)?);
+        }
+
+        Ok(Expr::String(path.to_string_lossy().into()))
+    });
+    scope.register_fn("path/basename", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        Ok(as_path(ctx, path)?
+            .file_name()
+            .map_or(Expr::Nil, |n| Expr::String(n.to_string_lossy().into())))
+    });
+    scope.register_fn("path/extension", |ctx, args| {
+        let [path] = ctx.get_n(args)?;
+        Ok(as_path(ctx, path)?
+            .extension()
+            .map_or(Expr::Nil, |n| Expr::String(n.to_string_lossy().into())))
+    });
+}
+
+/// Get a path from a string argument
+fn as_path<'a>(ctx: &Context, expr: &'a Expr) -> Result<&'a Path, Error> {
+    match expr {
+        Expr::String(s) => Ok(Path::new(&**s)),
+        _ => Err(ctx.error(ErrorKind::InvalidArgument(expr.clone()))),
+    }
+}
+
+/// Turn an IO error into an evaluation error, mentioning the path it happened on
+fn io_error(ctx: &Context, path: &Path, err: &std::io::Error) -> Error {
+    ctx.error(ErrorKind::Io(format!("`{}`: {err}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::prelude::{Context, EvalErrorKind, Expr, Parser};
+
+    eval_test!(path_join: r#"(path/join "a" "b" "c.fy")"#, Ok({
+        let path = std::path::Path::new("a").join("b").join("c.fy");
+        Expr::String(path.to_string_lossy().into()).to_string()
+    }));
+
+    eval_test!(path_basename: r#"(path/basename "a/b/c.fy")"#, Ok(r#""c.fy""#.to_owned()));
+
+    eval_test!(path_extension: r#"(path/extension "a/b/c")"#, Ok("nil".to_owned()));
+
+    eval_test!(error_path: "(slurp 1)", Err(EvalErrorKind::InvalidArgument(Expr::Number(1.))));
+
+    #[test]
+    fn files() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("faye-fs-test-{}", std::process::id()));
+        let dir = dir.to_string_lossy();
+        let code = format!(
+            r#"(mkdir "{dir}")
+               (spit (path/join "{dir}" "a.txt") "one\n")
+               (append-file (path/join "{dir}" "a.txt") "two")
+               (slurp (path/join "{dir}" "a.txt"))
+               (read-lines (path/join "{dir}" "a.txt"))
+               (list-dir "{dir}")
+               (delete-file (path/join "{dir}" "a.txt"))
+               (file-exists? (path/join "{dir}" "a.txt"))
+               (delete-file "{dir}")
+               (slurp "{dir}")"#
+        );
+        let ast = Parser::new(&code).parse()?;
+        let mut ctx = Context::default();
+        let res = ast.iter().map(|n| ctx.eval(n)).collect::<Vec<_>>();
+
+        assert_eq!(
+            res[3].as_ref().map(ToString::to_string),
+            Ok(r#""one\ntwo""#.to_owned())
+        );
+        assert_eq!(
+            res[4].as_ref().map(ToString::to_string),
+            Ok(r#"["one" "two"]"#.to_owned())
+        );
+        assert_eq!(
+            res[5].as_ref().map(ToString::to_string),
+            Ok(r#"["a.txt"]"#.to_owned())
+        );
+        assert_eq!(res[7], Ok(Expr::Bool(false)));
+        assert!(res[8].is_ok());
+        assert!(matches!(
+            res[9].as_ref().map_err(|e| &e.kind),
+            Err(EvalErrorKind::Io(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::{Map, Number, Value};
+
+use super::{Context, Error, ErrorKind, Expr, Scope};
+use crate::prelude::Span;
+
+/// Register the `json/` builtins
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register_fn("json/parse", |ctx, args| {
+        let [string] = ctx.get_n(args)?;
+        let string = ctx.downcast::<String>(string)?;
+        // every value takes at least a byte of the input, so this bounds what parsing creates
+        ctx.alloc(string.len())?;
+
+        serde_json::from_str(&string).map(from_json).map_err(|e| {
+            // the message from serde_json ends with its own location
+            let msg = e.to_string();
+            let msg = msg.split(" at line ").next().unwrap_or_default().to_owned();
+            Error::new(
+                ErrorKind::InvalidJson(msg, e.line(), e.column()),
+                Span::default(),
+            )
+        })
+    });
+    scope.register_fn("json/stringify", |ctx, args| {
+        let (value, pretty) = match ctx.get_n(args) {
+            Ok([value, pretty]) => (value, ctx.downcast::<bool>(pretty)?),
+            Err(_) => {
+                let [value] = ctx.get_n(args)?;
+                (value, false)
+            }
+        };
+
+        let json = to_json(ctx, value)?;
+        let string = if pretty {
+            serde_json::to_string_pretty(&json)
+        } else {
+            serde_json::to_string(&json)
+        };
+
+        Ok(Expr::String(
+            string.expect("json values always serialize").into(),
+        ))
+    });
+}
+
+/// Convert a JSON value, turning objects into association lists
+///
+/// An empty object becomes an empty list, which [`to_json`] writes back as an empty array
+fn from_json(value: Value) -> Expr {
+    match value {
+        Value::Null => Expr::Nil,
+        Value::Bool(b) => Expr::Bool(b),
+        Value::Number(n) => Expr::Number(n.as_f64().unwrap_or(f64::NAN)),
+        Value::String(s) => Expr::String(s.into()),
+        Value::Array(v) => Expr::Vector(v.into_iter().map(from_json).collect()),
+        Value::Object(m) => Expr::List(
+            m.into_iter()
+                .map(|(k, v)| Expr::List([Expr::String(k.into()), from_json(v)].into()))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a value to JSON, turning association lists and records into objects
+///
+/// An empty list is written as an empty array, as it holds no pairs to tell it apart from one
+fn to_json(ctx: &Context, value: &Expr) -> Result<Value, Error> {
+    Ok(match value {
+        Expr::Nil => Value::Null,
+        Expr::Bool(b) => Value::Bool(*b),
+        #[allow(clippy::cast_possible_truncation)]
+        Expr::Number(n) if n.fract() == 0. && n.abs() < 2f64.powi(53) => Value::from(*n as i64),
+        Expr::Number(n) => Number::from_f64(*n)
+            .map(Value::Number)
+            .ok_or_else(|| ctx.error(ErrorKind::InvalidArgument(value.clone())))?,
+        Expr::String(s) => Value::String(s.to_string()),
+        Expr::Char(c) => Value::String(c.to_string()),
+        Expr::Keyword(k) => Value::String(k.clone()),
+        Expr::Symbol(s) => Value::String(s.to_string()),
+        Expr::List(v) if !v.is_empty() && v.iter().all(|e| key(e).is_some()) => {
+            let mut map = Map::new();
+            for pair in v.iter() {
+                let (k, v) = key(pair).expect("checked to be a pair");
+                map.insert(k, to_json(ctx, v)?);
+            }
+            Value::Object(map)
+        }
+        Expr::Record(r) => Value::Object(
+            r.iter()
+                .map(|(k, v)| Ok((k.to_string(), to_json(ctx, v)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Expr::List(v) | Expr::Vector(v) => Value::Array(
+            v.iter()
+                .map(|e| to_json(ctx, e))
+                .collect::<Result<_, _>>()?,
+        ),
+        _ => return Err(ctx.error(ErrorKind::InvalidArgument(value.clone()))),
+    })
+}
+
+/// Get the key and value of an association list entry
+fn key(pair: &Expr) -> Option<(String, &Expr)> {
+    let Expr::List(pair) = pair else {
+        return None;
+    };
+    let [k, v] = &pair[..] else {
+        return None;
+    };
+
+    match k {
+        Expr::String(s) => Some((s.to_string(), v)),
+        Expr::Keyword(k) => Some((k.clone(), v)),
+        Expr::Symbol(s) => Some((s.to_string(), v)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::prelude::{Context, EvalErrorKind, Expr, Parser};
+
+    eval_test!(parse: r#"(json/parse "{\"b\": [1, 2.5, null], \"a\": {\"c\": true}, \"d\": {}}")"#, Ok(
+        r#"(("b" [1 2.5 nil]) ("a" (("c" true))) ("d" ()))"#.to_owned()
+    ));
+
+    eval_test!(stringify: r#"(json/stringify (list (list :name "faye") (list "tags" [1 'x' nil])))"#, Ok(
+        r#""{\"name\":\"faye\",\"tags\":[1,\"x\",null]}""#.to_owned()
+    ));
+
+    eval_test!(empty_collections: r#"(vector (json/parse "{}") (json/parse "[]") (json/stringify (list)) (json/stringify (json/parse "{}")))"#, Ok(
+        r#"[() [] "[]" "[]"]"#.to_owned()
+    ));
+
+    eval_test!(stringify_pretty: "(json/stringify [1] true)", Ok(
+        r#""[\n  1\n]""#.to_owned()
+    ));
+
+    eval_test!(error_parse: r#"(json/parse "[1,\n  2")"#, Err(
+        EvalErrorKind::InvalidJson("EOF while parsing a list".to_owned(), 2, 3)
+    ));
+
+    eval_test!(error_stringify_pretty: "(json/stringify [1] 1)", Err(
+        EvalErrorKind::InvalidArgument(Expr::Number(1.))
+    ));
+
+    #[test]
+    fn error_stringify_function() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new("(json/stringify (list 1 +))").parse()?;
+        let res = Context::default().eval(&ast[0]).map_err(|e| e.kind);
+
+        assert!(matches!(
+            res,
+            Err(EvalErrorKind::InvalidArgument(Expr::BuiltinFn(_)))
+        ));
+        Ok(())
+    }
+}
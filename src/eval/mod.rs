@@ -3,20 +3,48 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+pub use budget::Limit;
+pub use builder::{Capability, ContextBuilder};
 pub use builtin::BuiltinFn;
 pub use closure::Closure;
 pub use context::Context;
+pub use convert::{FromExpr, IntoBuiltin, IntoExpr, IntoReturn};
+pub use env::Env;
 pub use error::{Error, ErrorKind};
 pub use expr::Expr;
+pub use opaque::Opaque;
+pub use record::Record;
 pub use scope::Scope;
+pub use streams::{InputSource, Sink};
+pub use test::Test;
 pub use userfn::UserFn;
 
+mod budget;
+mod builder;
 mod builtin;
 mod closure;
 mod context;
+mod convert;
+mod env;
 mod error;
 mod expr;
+mod format;
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "json")]
+mod json;
+mod opaque;
+mod process;
+mod record;
+#[cfg(feature = "regex")]
+pub(crate) mod regex;
 mod scope;
+#[cfg(feature = "serde")]
+mod serialize;
+mod streams;
+mod strings;
+mod test;
+mod types;
 mod userfn;
 
 #[cfg(test)]
@@ -36,6 +64,43 @@ mod tests {
         };
     }
 
+    /// Evaluate every form of the input in one context and check the last value, comparing how
+    /// it is displayed, or with `str`, the string it evaluates to
+    macro_rules! eval_test {
+        ($name:ident: $input:literal, str $res:expr) => {
+            #[test]
+            fn $name() -> Result<(), Box<dyn std::error::Error>> {
+                let res = $crate::eval::tests::eval_all($input)?;
+                let expected = $res.map(|s: &str| $crate::eval::Expr::String(s.into()));
+
+                assert_eq!(res.map_err(|e| e.kind), expected);
+                Ok(())
+            }
+        };
+        ($name:ident: $input:literal, $res:expr) => {
+            #[test]
+            fn $name() -> Result<(), Box<dyn std::error::Error>> {
+                let res = $crate::eval::tests::eval_all($input)?;
+
+                assert_eq!(res.map(|v| v.to_string()).map_err(|e| e.kind), $res);
+                Ok(())
+            }
+        };
+    }
+    pub(crate) use eval_test;
+
+    /// Evaluate every form of the input in a new context, returning the value of the last one
+    pub(crate) fn eval_all(input: &str) -> Result<Result<Expr, Error>, crate::parser::Error> {
+        let ast = crate::parser::Parser::new(input).parse()?;
+        let mut ctx = Context::default();
+
+        Ok(ast
+            .iter()
+            .map(|node| ctx.eval(node))
+            .last()
+            .unwrap_or(Ok(Expr::Nil)))
+    }
+
     test!(mul: "(* 2 3)", Ok(Expr::Number(6.)));
 
     test!(error_invalid_function: "(1 + 2)", Err(
@@ -45,4 +110,26 @@ mod tests {
     test!(error_add_string: "(+ \"hi\" 5)", Err(
         ErrorKind::InvalidArgument(Expr::String("hi".into()))
     ));
+
+    test!(closure_captures_environment: "(let (x 1) (let (f (lambda [] x)) (x 2) (list x (f))))", Ok(
+        Expr::List(vec![Expr::Number(2.), Expr::Number(1.)].into())
+    ));
+
+    test!(let_shadows_same_frame: "(let (x 1) (x 2) x)", Ok(Expr::Number(2.)));
+
+    #[test]
+    fn cons_cells_share_tails() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = crate::parser::Parser::new("(const tail (list 2 nil)) (list 1 tail)").parse()?;
+        let mut ctx = Context::default();
+        ctx.eval(&ast[0])?;
+        let cell = ctx.eval(&ast[1])?;
+
+        let (Some(Expr::List(tail)), Expr::List(cell)) =
+            (ctx.get(&crate::prelude::Symbol::from("tail")), &cell)
+        else {
+            panic!("expected two lists");
+        };
+        assert!(matches!(&cell[1], Expr::List(t) if std::rc::Rc::ptr_eq(t, tail)));
+        Ok(())
+    }
 }
@@ -0,0 +1,150 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{any::Any, rc::Rc};
+
+/// type alias for the equality of opaque values, whose first argument is of the wrapped type
+type EqFn = fn(&dyn Any, &dyn Any) -> bool;
+
+/// A value owned by the host application, which faye code can only pass around
+#[derive(Clone)]
+pub struct Opaque {
+    value: Rc<dyn Any>,
+    name: &'static str,
+    eq: Option<EqFn>,
+}
+
+impl Opaque {
+    /// Wrap a host value, which is only equal to itself and its clones
+    #[must_use]
+    pub fn new<T: Any>(value: T) -> Self {
+        Self {
+            value: Rc::new(value),
+            name: short_name(std::any::type_name::<T>()),
+            eq: None,
+        }
+    }
+
+    /// Wrap a host value that is compared by its [`PartialEq`] implementation
+    #[must_use]
+    pub fn with_eq<T: Any + PartialEq>(value: T) -> Self {
+        Self {
+            eq: Some(|a, b| a.downcast_ref::<T>() == b.downcast_ref::<T>()),
+            ..Self::new(value)
+        }
+    }
+
+    /// Get the name of the wrapped type
+    #[must_use]
+    pub const fn type_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Check whether the wrapped value is of a specific type
+    #[must_use]
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    /// Get a reference to the wrapped value if it is of a specific type
+    #[must_use]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// Get a shared handle to the wrapped value if it is of a specific type
+    #[must_use]
+    pub fn downcast<T: Any>(&self) -> Option<Rc<T>> {
+        self.value.clone().downcast().ok()
+    }
+}
+
+/// Strip the module path from a type name, keeping any generic arguments
+fn short_name(name: &'static str) -> &'static str {
+    let end = name.find('<').unwrap_or(name.len());
+    name[..end].rfind("::").map_or(name, |i| &name[i + 2..])
+}
+
+impl PartialEq for Opaque {
+    fn eq(&self, other: &Self) -> bool {
+        if (*self.value).type_id() != (*other.value).type_id() {
+            return false;
+        }
+
+        // values are only compared by contents when both sides opted in, so that `=` is symmetric
+        match (self.eq, other.eq) {
+            (Some(eq), Some(_)) => eq(&*self.value, &*other.value),
+            _ => Rc::ptr_eq(&self.value, &other.value),
+        }
+    }
+}
+
+impl std::fmt::Debug for Opaque {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Opaque").field(&self.name).finish()
+    }
+}
+
+impl std::fmt::Display for Opaque {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#<{}>", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Opaque;
+    use crate::prelude::{Context, EvalErrorKind as ErrorKind, Expr, Parser};
+
+    #[test]
+    fn eq_symmetric() {
+        #[derive(PartialEq)]
+        struct Entity(u32);
+
+        let (a, b) = (Opaque::with_eq(Entity(1)), Opaque::new(Entity(1)));
+        assert_eq!(a == b, b == a);
+        assert_ne!(a, b);
+        assert_eq!(a, Opaque::with_eq(Entity(1)));
+        assert_ne!(Opaque::with_eq(1_u32), Opaque::with_eq(1_u64));
+    }
+
+    #[test]
+    fn opaque() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(PartialEq)]
+        struct Entity(u32);
+        struct Pool;
+
+        let ast = Parser::new(
+            "(entity 1) (id (entity 2)) (= (entity 3) (entity 3)) (= (pool) (pool)) (id (pool)) (id 1)",
+        )
+        .parse()?;
+        let mut ctx = Context::default();
+        ctx.register("entity", |id: usize| Opaque::with_eq(Entity(id as u32)));
+        ctx.register("pool", || Opaque::new(Pool));
+        ctx.register("id", |e: Rc<Entity>| e.0 as usize);
+
+        let res = ast
+            .iter()
+            .map(|n| ctx.eval(n).map(|v| v.to_string()).map_err(|e| e.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            res[..4],
+            [
+                Ok("#<Entity>".to_owned()),
+                Ok("2".to_owned()),
+                Ok("true".to_owned()),
+                Ok("false".to_owned()),
+            ]
+        );
+        assert!(matches!(
+            &res[4],
+            Err(ErrorKind::InvalidArgument(Expr::Opaque(v))) if v.is::<Pool>()
+        ));
+        assert_eq!(res[5], Err(ErrorKind::InvalidArgument(Expr::Number(1.))));
+        Ok(())
+    }
+}
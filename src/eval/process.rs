@@ -0,0 +1,244 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use super::{Context, Error, ErrorKind, Expr, Scope};
+
+/// Register the builtins that spawn other programs
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register_fn("sh", |ctx, args| {
+        let [script] = ctx.get_n(args)?;
+        let Expr::String(script) = script else {
+            return Err(ctx.error(ErrorKind::InvalidArgument(script.clone())));
+        };
+
+        let mut cmd = command(ctx, if cfg!(windows) { "cmd" } else { "sh" });
+        cmd.arg(if cfg!(windows) { "/C" } else { "-c" })
+            .arg(&**script);
+
+        run(ctx, cmd, "sh", None)
+    });
+    scope.register_fn("exec", |ctx, args| {
+        let (argv, options) = match ctx.get_n(args) {
+            Ok([argv, options]) => (argv, options),
+            Err(_) => {
+                let [argv] = ctx.get_n(args)?;
+                (argv, &Expr::Nil)
+            }
+        };
+
+        let argv = ctx.downcast::<Vec<String>>(argv)?;
+        let (program, rest) = argv
+            .split_first()
+            .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+        let mut cmd = command(ctx, program);
+        cmd.args(rest);
+
+        let mut stdin = None;
+        for (key, value) in pairs(ctx, options)? {
+            match key.as_str() {
+                "stdin" => stdin = Some(string(ctx, value)?),
+                "dir" => _ = cmd.current_dir(string(ctx, value)?),
+                "env" => {
+                    for (name, value) in pairs(ctx, value)? {
+                        cmd.env(name, string(ctx, value)?);
+                    }
+                }
+                _ => return Err(ctx.error(ErrorKind::InvalidArgument(options.clone()))),
+            }
+        }
+
+        run(ctx, cmd, program, stdin)
+    });
+}
+
+/// Create a command that sees the environment variables changed with `setenv`
+fn command(ctx: &Context, program: &str) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.envs(ctx.vars.iter().filter_map(|(k, v)| Some((k, v.as_ref()?))));
+    for (key, _) in ctx.vars.iter().filter(|(_, v)| v.is_none()) {
+        cmd.env_remove(key);
+    }
+
+    cmd
+}
+
+/// Run a command to completion, returning its exit code, stdout and stderr
+///
+/// The command is killed if the evaluation runs out of time or is cancelled while it runs
+fn run(
+    ctx: &mut Context,
+    mut cmd: Command,
+    name: &str,
+    stdin: Option<String>,
+) -> Result<Expr, Error> {
+    let io_error =
+        |ctx: &Context, e: std::io::Error| ctx.error(ErrorKind::Io(format!("`{name}`: {e}")));
+
+    let mut child = cmd
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io_error(ctx, e))?;
+
+    // write from another thread, so a child that fills its output before reading cannot block us
+    let writer = child
+        .stdin
+        .take()
+        .zip(stdin)
+        .map(|(mut pipe, input)| std::thread::spawn(move || pipe.write_all(input.as_bytes())));
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    // poll the child, so that it can be killed once the budget runs out or it is cancelled
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| io_error(ctx, e))? {
+            break status;
+        }
+        if let Err(e) = ctx.interrupted() {
+            _ = child.kill();
+            _ = child.wait();
+            return Err(e);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    if let Some(writer) = writer {
+        // a child that exits without reading all of its input is not an error
+        _ = writer.join();
+    }
+    let join = |reader: Option<JoinHandle<std::io::Result<Vec<u8>>>>| {
+        reader
+            .map(|r| r.join().expect("reader thread does not panic"))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    };
+    let stdout = join(stdout).map_err(|e| io_error(ctx, e))?;
+    let stderr = join(stderr).map_err(|e| io_error(ctx, e))?;
+
+    ctx.alloc(stdout.len() + stderr.len())?;
+    Ok(Expr::Vector(
+        [
+            status
+                .code()
+                .map_or(Expr::Nil, |c| Expr::Number(f64::from(c))),
+            Expr::String(String::from_utf8_lossy(&stdout).into()),
+            Expr::String(String::from_utf8_lossy(&stderr).into()),
+        ]
+        .into(),
+    ))
+}
+
+/// Read a pipe until it is closed, from another thread
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> JoinHandle<std::io::Result<Vec<u8>>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        pipe.read_to_end(&mut buf).map(|_| buf)
+    })
+}
+
+/// Get the keys and values of an association list, with string, keyword or symbol keys
+fn pairs<'a>(ctx: &Context, expr: &'a Expr) -> Result<Vec<(String, &'a Expr)>, Error> {
+    let invalid = || ctx.error(ErrorKind::InvalidArgument(expr.clone()));
+    let pairs = match expr {
+        Expr::Nil => return Ok(Vec::new()),
+        Expr::List(v) | Expr::Vector(v) => v,
+        _ => return Err(invalid()),
+    };
+
+    pairs
+        .iter()
+        .map(|pair| match pair {
+            Expr::List(pair) | Expr::Vector(pair) => match &pair[..] {
+                [Expr::String(k), v] => Ok((k.to_string(), v)),
+                [Expr::Keyword(k), v] => Ok((k.clone(), v)),
+                [Expr::Symbol(k), v] => Ok((k.to_string(), v)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+/// Get the contents of a string argument
+fn string(ctx: &Context, expr: &Expr) -> Result<String, Error> {
+    match expr {
+        Expr::String(s) => Ok(s.to_string()),
+        _ => Err(ctx.error(ErrorKind::InvalidArgument(expr.clone()))),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::eval::{ContextBuilder, Limit};
+    use crate::prelude::{Context, EvalErrorKind, Expr, Parser};
+
+    eval_test!(sh: r#"(sh "echo hi; echo oops >&2; exit 3")"#, Ok(
+        r#"[3 "hi\n" "oops\n"]"#.to_owned()
+    ));
+
+    eval_test!(exec: r#"(exec ["sh" "-c" "cat; echo $FAYE_TEST; pwd"] (list (list :stdin "in ") (list :env (list (list "FAYE_TEST" "env"))) (list :dir "/")))"#, Ok(
+        r#"[0 "in env\n/\n" ""]"#.to_owned()
+    ));
+
+    eval_test!(error_exec_option: r#"(exec ["true"] (list (list :cwd "/")))"#, Err(
+        EvalErrorKind::InvalidArgument(Expr::List(
+            [Expr::List([Expr::Keyword("cwd".to_owned()), Expr::String("/".into())].into())].into()
+        ))
+    ));
+
+    #[test]
+    fn setenv() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(
+            r#"(setenv "FAYE_PROCESS_TEST" "set") (setenv "HOME" nil) (sh "echo $FAYE_PROCESS_TEST; echo ${HOME-unset}")"#,
+        )
+        .parse()?;
+        let mut ctx = Context::default();
+        let res = ast.iter().map(|n| ctx.eval(n)).last().transpose()?;
+
+        assert_eq!(
+            res.map(|v| v.to_string()),
+            Some(r#"[0 "set\nunset\n" ""]"#.to_owned())
+        );
+        assert!(std::env::var("FAYE_PROCESS_TEST").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn error_timeout() -> Result<(), Box<dyn std::error::Error>> {
+        let timeout = std::time::Duration::from_millis(50);
+        let ast = Parser::new(r#"(sh "sleep 5")"#).parse()?;
+        let mut ctx = ContextBuilder::all().timeout(timeout).build();
+
+        let start = std::time::Instant::now();
+        let res = ctx.eval(&ast[0]).map_err(|e| e.kind);
+        assert_eq!(
+            res,
+            Err(EvalErrorKind::BudgetExceeded(Limit::Timeout(timeout)))
+        );
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        Ok(())
+    }
+
+    #[test]
+    fn error_exec_missing() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(r#"(exec ["faye-does-not-exist"])"#).parse()?;
+        let res = Context::default().eval(&ast[0]).map_err(|e| e.kind);
+
+        assert!(matches!(res, Err(EvalErrorKind::Io(_))));
+        Ok(())
+    }
+}
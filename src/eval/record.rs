@@ -0,0 +1,177 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::rc::Rc;
+
+use super::{BuiltinFn, ErrorKind, Expr, Scope};
+use crate::prelude::Symbol;
+
+/// An instance of a record type defined with `defrecord`
+///
+/// Records are equal when they are of the same type and their fields are equal
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub(crate) name: Symbol,
+    pub(crate) fields: Rc<[Symbol]>,
+    pub(crate) values: Rc<[Expr]>,
+}
+
+impl Record {
+    /// Get the value of a field by name
+    #[must_use]
+    pub fn get(&self, field: &Symbol) -> Option<&Expr> {
+        let i = self.fields.iter().position(|f| f == field)?;
+        self.values.get(i)
+    }
+
+    /// Iterate over the fields of the record and their values, in the order they were defined
+    pub fn iter(&self) -> impl Iterator<Item = (&Symbol, &Expr)> {
+        self.fields.iter().zip(self.values.iter())
+    }
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}{{", self.name)?;
+        for (i, (field, value)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, ":{field} {value}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Register `defrecord`, which defines a constructor, a predicate and field accessors, and
+/// `record-name`, which gets the name of the type of a record
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register_fn("record-name", |ctx, args| {
+        let [value] = ctx.get_n(args)?;
+        match value {
+            Expr::Record(r) => Ok(Expr::Symbol(r.name)),
+            _ => Err(ctx.error(ErrorKind::InvalidArgument(value.clone()))),
+        }
+    });
+
+    scope.register("defrecord", |ctx, args| {
+        let [name, fields] = ctx.get_n(args)?;
+        let name = ctx.downcast::<Symbol>(&Expr::from(name))?;
+        let fields: Rc<[Symbol]> = match Expr::from(fields) {
+            f @ (Expr::Vector(_) | Expr::Nil) => ctx.downcast::<Vec<Symbol>>(&f)?.into(),
+            f => return Err(ctx.error(ErrorKind::InvalidArgument(f))),
+        };
+        if let Some(dup) = fields
+            .iter()
+            .enumerate()
+            .find(|(i, f)| fields[..*i].contains(f))
+        {
+            return Err(ctx.error(ErrorKind::InvalidArgument(Expr::Symbol(*dup.1))));
+        }
+
+        let constructor = {
+            let (fields, arity) = (fields.clone(), fields.len());
+            BuiltinFn::function(name.to_string(), move |ctx, args| {
+                if args.len() < fields.len() {
+                    return Err(ctx.error(ErrorKind::MissingArguments));
+                } else if args.len() > fields.len() {
+                    return Err(ctx.error(ErrorKind::TooManyArguments));
+                }
+                ctx.alloc(args.len())?;
+
+                Ok(Expr::Record(Record {
+                    name,
+                    fields: fields.clone(),
+                    values: args.into(),
+                }))
+            })
+            .with_arity(arity)
+        };
+        // each definition has its own fields, so records of a type that was since redefined with
+        // the same name are told apart by them
+        let is_type = {
+            let fields = fields.clone();
+            move |r: &Record| Rc::ptr_eq(&r.fields, &fields)
+        };
+        let predicate = {
+            let is_type = is_type.clone();
+            BuiltinFn::function(format!("{name}?"), move |ctx, args| {
+                let [value] = ctx.get_n(args)?;
+                Ok(Expr::Bool(matches!(value, Expr::Record(r) if is_type(r))))
+            })
+            .with_arity(1)
+        };
+
+        ctx.globals.insert(name, Expr::BuiltinFn(constructor));
+        ctx.globals
+            .insert(Symbol::from(format!("{name}?")), Expr::BuiltinFn(predicate));
+        for (i, field) in fields.iter().enumerate() {
+            let accessor = format!("{name}-{field}");
+            let is_type = is_type.clone();
+            let getter = BuiltinFn::function(accessor.clone(), move |ctx, args| {
+                let [value] = ctx.get_n(args)?;
+                match value {
+                    Expr::Record(r) if is_type(r) => Ok(r.values[i].clone()),
+                    _ => Err(ctx.error(ErrorKind::InvalidArgument(value.clone()))),
+                }
+            })
+            .with_arity(1);
+            ctx.globals
+                .insert(Symbol::from(accessor), Expr::BuiltinFn(getter));
+        }
+
+        Ok(Expr::Nil)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Record;
+    use crate::eval::tests::{eval_all, eval_test};
+    use crate::prelude::{EvalErrorKind, Expr, Symbol};
+
+    eval_test!(record: "(defrecord Point [x y]) (Point 1 2)", Ok("#Point{:x 1 :y 2}".to_owned()));
+
+    eval_test!(accessors: "(defrecord Point [x y]) (let (p (Point 1 \"two\")) (list (Point-y p) (Point? p) (Point? [1 2])))", Ok(
+        r#"("two" true false)"#.to_owned()
+    ));
+
+    eval_test!(equality: "(defrecord A [x]) (defrecord B [x]) (list (= (A 1) (A 1)) (= (A 1) (A 2)) (= (A 1) (B 1)))", Ok(
+        "(true false false)".to_owned()
+    ));
+
+    eval_test!(record_name: "(defrecord Point [x]) (list (type-of (Point 1)) (record-name (Point 1)) (arity Point) (arity Point-x))", Ok(
+        "(:record Point 1 1)".to_owned()
+    ));
+
+    eval_test!(error_record_name: "(record-name [1])", Err(EvalErrorKind::InvalidArgument(
+        Expr::Vector([Expr::Number(1.)].into())
+    )));
+
+    eval_test!(redefined: "(defrecord P [x]) (const p (P 1)) (defrecord P [x y]) (list (P? p) (P? (P 1 2)) (P-y (P 1 2)))", Ok(
+        "(false true 2)".to_owned()
+    ));
+
+    #[test]
+    fn error_redefined_accessor() -> Result<(), Box<dyn std::error::Error>> {
+        let res = eval_all("(defrecord P [x]) (const p (P 1)) (defrecord P [x y]) (P-y p)")?;
+
+        assert!(matches!(
+            res.map_err(|e| e.kind),
+            Err(EvalErrorKind::InvalidArgument(Expr::Record(r))) if r.name == Symbol::from("P")
+        ));
+        Ok(())
+    }
+
+    eval_test!(error_arity: "(defrecord Point [x y]) (Point 1)", Err(EvalErrorKind::MissingArguments));
+
+    eval_test!(error_accessor_type: "(defrecord A [x]) (defrecord B [x]) (A-x (B 1))", Err(
+        EvalErrorKind::InvalidArgument(Expr::Record(Record {
+            name: Symbol::from("B"),
+            fields: [Symbol::from("x")].into(),
+            values: [Expr::Number(1.)].into(),
+        }))
+    ));
+}
@@ -0,0 +1,214 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{cell::RefCell, collections::HashMap};
+
+use super::{Context, Error, ErrorKind, Expr, Opaque, Scope};
+
+/// A compiled regular expression, as created by a `#"..."` literal
+#[derive(Debug, Clone)]
+pub struct Regex(regex::Regex);
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+thread_local! {
+    /// Patterns compiled from strings or literals, so they are not recompiled in loops
+    static CACHE: RefCell<HashMap<String, regex::Regex>> = RefCell::default();
+}
+
+/// Compile a pattern, reusing a previous compilation of it
+pub(crate) fn compile(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    if let Some(re) = CACHE.with_borrow(|c| c.get(pattern).cloned()) {
+        return Ok(re);
+    }
+
+    let re = regex::Regex::new(pattern)?;
+    CACHE.with_borrow_mut(|c| {
+        if c.len() >= 256 {
+            c.clear();
+        }
+        c.insert(pattern.to_owned(), re.clone());
+    });
+    Ok(re)
+}
+
+/// Turn a regular expression literal into a value
+///
+/// # Panics
+///
+/// Panics if the pattern does not compile, which the parser rules out for parsed literals
+pub(crate) fn literal(pattern: &str) -> Expr {
+    let re = compile(pattern).expect("regex literals are compiled by the parser");
+    Expr::Opaque(Opaque::with_eq(Regex(re)))
+}
+
+/// Compile a pattern so that it only matches whole strings
+fn anchored(ctx: &Context, pattern: &str) -> Result<regex::Regex, Error> {
+    use regex_syntax::hir::{Hir, Look};
+
+    // anchoring the parsed pattern keeps trailing comments in `(?x)` patterns from eating the `$`
+    let hir = regex_syntax::Parser::new()
+        .parse(pattern)
+        .map_err(|e| ctx.error(ErrorKind::InvalidRegex(e.to_string())))?;
+    let hir = Hir::concat(vec![Hir::look(Look::Start), hir, Hir::look(Look::End)]);
+
+    compile(&hir.to_string()).map_err(|e| ctx.error(ErrorKind::InvalidRegex(e.to_string())))
+}
+
+/// Register the `re-` builtins, which take a regular expression or a string pattern
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register_fn("re-find", |ctx, args| {
+        let [re, s] = ctx.get_n(args)?;
+        let (re, s) = (regex(ctx, re)?, as_str(ctx, s)?);
+
+        Ok(re.captures(s).map_or(Expr::Nil, |c| captures(&c)))
+    });
+    scope.register_fn("re-matches", |ctx, args| {
+        let [re, s] = ctx.get_n(args)?;
+        let (re, s) = (regex(ctx, re)?, as_str(ctx, s)?);
+        let whole = anchored(ctx, re.as_str())?;
+
+        Ok(whole.captures(s).map_or(Expr::Nil, |c| captures(&c)))
+    });
+    scope.register_fn("re-seq", |ctx, args| {
+        let [re, s] = ctx.get_n(args)?;
+        let (re, s) = (regex(ctx, re)?, as_str(ctx, s)?);
+        let matches = re
+            .captures_iter(s)
+            .map(|c| captures(&c))
+            .collect::<Vec<_>>();
+        ctx.alloc(matches.len())?;
+
+        Ok(Expr::Vector(matches.into()))
+    });
+    scope.register_fn("re-replace", |ctx, args| {
+        let [re, s, replacement] = ctx.get_n(args)?;
+        let (re, s) = (regex(ctx, re)?, as_str(ctx, s)?);
+        let string = re.replace_all(s, as_str(ctx, replacement)?).into_owned();
+        ctx.alloc(string.len())?;
+
+        Ok(Expr::String(string.into()))
+    });
+    scope.register_fn("re-split", |ctx, args| {
+        let [re, s] = ctx.get_n(args)?;
+        let (re, s) = (regex(ctx, re)?, as_str(ctx, s)?);
+        let parts = re
+            .split(s)
+            .map(|p| Expr::String(p.into()))
+            .collect::<Vec<_>>();
+        ctx.alloc(parts.len())?;
+
+        Ok(Expr::Vector(parts.into()))
+    });
+}
+
+/// Get a regular expression from a literal or a string pattern
+fn regex(ctx: &Context, expr: &Expr) -> Result<regex::Regex, Error> {
+    match expr {
+        Expr::Opaque(o) if o.is::<Regex>() => {
+            Ok(o.downcast_ref::<Regex>().expect("checked type").0.clone())
+        }
+        Expr::String(s) => compile(s).map_err(|e| {
+            let msg = match e {
+                regex::Error::Syntax(msg) => {
+                    let msg = msg.lines().last().unwrap_or_default();
+                    msg.trim_start_matches("error: ").to_owned()
+                }
+                e => e.to_string(),
+            };
+            ctx.error(ErrorKind::InvalidRegex(msg))
+        }),
+        _ => Err(ctx.error(ErrorKind::InvalidArgument(expr.clone()))),
+    }
+}
+
+/// Get the contents of a string argument
+fn as_str<'a>(ctx: &Context, expr: &'a Expr) -> Result<&'a str, Error> {
+    match expr {
+        Expr::String(s) => Ok(s),
+        _ => Err(ctx.error(ErrorKind::InvalidArgument(expr.clone()))),
+    }
+}
+
+/// Get the matched string, or a vector of it and its groups if the pattern has any
+fn captures(c: &regex::Captures) -> Expr {
+    let group = |m: Option<regex::Match>| m.map_or(Expr::Nil, |m| Expr::String(m.as_str().into()));
+    if c.len() == 1 {
+        group(c.get(0))
+    } else {
+        Expr::Vector(c.iter().map(group).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::prelude::{EvalErrorKind, Expr, LexerErrorKind, Parser, ParserErrorKind};
+
+    eval_test!(find: r#"(re-find #"\d+" "abc 123 45")"#, Ok(r#""123""#.to_owned()));
+
+    eval_test!(find_groups: r#"(re-find #"(\w+)=(\d+)?" "key= x")"#, Ok(r#"["key=" "key" nil]"#.to_owned()));
+
+    eval_test!(matches: r#"(list (re-matches #"\d+" "123") (re-matches "\\d+" "123x"))"#, Ok(
+        r#"("123" nil)"#.to_owned()
+    ));
+
+    eval_test!(matches_extended: r#"(re-matches #"(?x) (\d+) # digits" "123")"#, Ok(
+        r#"["123" "123"]"#.to_owned()
+    ));
+
+    eval_test!(matches_alternation: r#"(re-matches #"a|ab" "ab")"#, Ok(r#""ab""#.to_owned()));
+
+    eval_test!(seq: r#"(re-seq #"[a-z]\"?" "a1b\"2c")"#, Ok(r#"["a" "b\"" "c"]"#.to_owned()));
+
+    eval_test!(replace: r#"(re-replace #"(\w+)@(\w+)" "me@host, you@there" "$2:$1")"#, Ok(
+        r#""host:me, there:you""#.to_owned()
+    ));
+
+    eval_test!(split: r#"(re-split #"\s*,\s*" "a , b,c")"#, Ok(r#"["a" "b" "c"]"#.to_owned()));
+
+    eval_test!(equal: r#"(= #"a+" #"a+")"#, Ok("true".to_owned()));
+
+    eval_test!(error_string_pattern: r#"(re-find "(" "x")"#, Err(
+        EvalErrorKind::InvalidRegex("unclosed group".to_owned())
+    ));
+
+    eval_test!(error_argument: r#"(re-find #"a" 1)"#, Err(EvalErrorKind::InvalidArgument(Expr::Number(1.))));
+
+    #[test]
+    fn error_literal() {
+        let err = Parser::new(r#"(re-find #"ab\"[z-a]" s)"#)
+            .parse()
+            .unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParserErrorKind::Lexer(crate::prelude::LexerError::new(
+                LexerErrorKind::InvalidRegex(
+                    "invalid character class range, the start must be <= the end".to_owned()
+                ),
+                err.span.clone(),
+            ))
+        );
+        assert_eq!(err.span.bytes, 16..19);
+    }
+
+    #[test]
+    fn error_literal_too_large() {
+        let err = Parser::new(r#"(re-find #"\w{1000}{1000}" s)"#)
+            .parse()
+            .unwrap_err();
+
+        assert!(matches!(
+            err.kind,
+            ParserErrorKind::Lexer(e) if matches!(e.kind, LexerErrorKind::InvalidRegex(_))
+        ));
+        assert_eq!(err.span.bytes, 9..26);
+    }
+}
@@ -3,174 +3,194 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{collections::HashMap, io::IsTerminal};
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
 use super::{
     builtin::{BuiltinFn, Callback},
     closure::Closure,
+    streams::Lines,
     userfn::UserFn,
-    Context, Error, ErrorKind, Expr,
+    Capability, Context, ContextBuilder, Env, Error, ErrorKind, Expr, FromExpr, Opaque, Test,
 };
-use crate::prelude::{Node, NodeKind, Symbol};
+use crate::lexer::BuildSymbolHasher;
+use crate::prelude::{Node, NodeKind, Parser, Symbol};
 
 /// A scope that stores functions
 #[derive(Debug, Default, Clone)]
-pub struct Scope(pub(crate) HashMap<Symbol, Expr>);
+pub struct Scope {
+    pub(crate) bindings: HashMap<Symbol, Expr, BuildSymbolHasher>,
+    /// Bumped whenever a special form is bound or replaced, so compiled code can tell it is stale
+    pub(crate) generation: u64,
+}
 
 impl Scope {
-    /// Create a new scope with builtin functions
-    pub(crate) fn builtins() -> Self {
+    /// Create a new scope with the builtin functions of the enabled capabilities
+    pub(crate) fn builtins(capabilities: &ContextBuilder) -> Self {
         let mut scope = Self::default();
 
-        scope.insert(
-            Symbol::from("@cmd-args"),
-            Expr::Vector(std::env::args().map(Expr::String).collect::<Vec<_>>()),
-        );
+        if capabilities.has(Capability::Core) {
+            scope.core();
+        }
+        if capabilities.has(Capability::Strings) {
+            scope.strings();
+        }
+        if capabilities.has(Capability::Io) {
+            scope.io();
+        }
+        #[cfg(feature = "fs")]
+        if capabilities.has(Capability::Fs) {
+            super::fs::register(&mut scope);
+        }
+        if capabilities.has(Capability::Env) {
+            scope.env();
+        }
+        if capabilities.has(Capability::Process) {
+            super::process::register(&mut scope);
+        }
 
-        scope.register("+", |ctx, args| {
-            Ok(Expr::Number(
-                ctx.eval_args(args)
-                    .and_then(|v| ctx.downcast_all::<f64>(&v))?
-                    .into_iter()
-                    .sum(),
-            ))
+        scope
+    }
+
+    /// Register the special forms, arithmetic, comparisons, collections and assertions
+    fn core(&mut self) {
+        self.register_fn("+", |ctx, args| {
+            args.iter()
+                .try_fold(0.0, |acc, v| Ok(acc + ctx.downcast::<f64>(v)?))
+                .map(Expr::Number)
         });
-        scope.register("*", |ctx, args| {
-            Ok(Expr::Number(
-                ctx.eval_args(args)
-                    .and_then(|v| ctx.downcast_all::<f64>(&v))?
-                    .into_iter()
-                    .product(),
-            ))
+        self.register_fn("*", |ctx, args| {
+            args.iter()
+                .try_fold(1.0, |acc, v| Ok(acc * ctx.downcast::<f64>(v)?))
+                .map(Expr::Number)
         });
-        scope.register("-", |ctx, args| {
-            Ok(Expr::Number(
-                ctx.eval_args(args)
-                    .and_then(|v| ctx.downcast_all::<f64>(&v))?
-                    .into_iter()
-                    .reduce(|acc, x| acc - x)
-                    .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?,
-            ))
+        self.register_fn("-", |ctx, args| {
+            let (first, rest) = args
+                .split_first()
+                .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+            rest.iter()
+                .try_fold(ctx.downcast::<f64>(first)?, |acc, v| {
+                    Ok(acc - ctx.downcast::<f64>(v)?)
+                })
+                .map(Expr::Number)
         });
-        scope.register("/", |ctx, args| {
-            Ok(Expr::Number(
-                ctx.eval_args(args)
-                    .and_then(|v| ctx.downcast_all::<f64>(&v))?
-                    .into_iter()
-                    .reduce(|acc, x| acc / x)
-                    .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?,
-            ))
+        self.register_fn("/", |ctx, args| {
+            let (first, rest) = args
+                .split_first()
+                .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+            rest.iter()
+                .try_fold(ctx.downcast::<f64>(first)?, |acc, v| {
+                    Ok(acc / ctx.downcast::<f64>(v)?)
+                })
+                .map(Expr::Number)
         });
-        scope.register("%", |ctx, args| {
-            Ok(Expr::Number(
-                ctx.eval_args(args)
-                    .and_then(|v| ctx.downcast_all::<f64>(&v))?
-                    .into_iter()
-                    .reduce(|acc, x| acc % x)
-                    .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?,
-            ))
+        self.register_fn("%", |ctx, args| {
+            let (first, rest) = args
+                .split_first()
+                .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+            rest.iter()
+                .try_fold(ctx.downcast::<f64>(first)?, |acc, v| {
+                    Ok(acc % ctx.downcast::<f64>(v)?)
+                })
+                .map(Expr::Number)
         });
-        scope.register("=", |ctx, args| {
-            let args = ctx.eval_args(args)?;
+        self.register_fn("=", |_, args| {
             Ok(Expr::Bool(args.iter().all(|n| n.eq(&args[0]))))
         });
-        scope.register("<", |ctx, args| ctx.compare::<f64>(args, |a, b| a < b));
-        scope.register(">", |ctx, args| ctx.compare::<f64>(args, |a, b| a > b));
-        scope.register("<=", |ctx, args| ctx.compare::<f64>(args, |a, b| a <= b));
-        scope.register(">=", |ctx, args| ctx.compare::<f64>(args, |a, b| a >= b));
-        scope.register("str", |ctx, args| {
-            Ok(Expr::String(
-                ctx.eval_args(args)
-                    .and_then(|v| ctx.downcast_all::<String>(&v))?
-                    .join(""),
-            ))
-        });
-        scope.register("chars", |ctx, args| {
+        self.register_fn("<", |ctx, args| ctx.compare::<f64>(args, |a, b| a < b));
+        self.register_fn(">", |ctx, args| ctx.compare::<f64>(args, |a, b| a > b));
+        self.register_fn("<=", |ctx, args| ctx.compare::<f64>(args, |a, b| a <= b));
+        self.register_fn(">=", |ctx, args| ctx.compare::<f64>(args, |a, b| a >= b));
+        self.register("quote", |ctx, args| {
             let [node] = ctx.get_n(args)?;
-            let string = ctx.downcast::<String>(&Expr::from(node))?;
+            Ok(Expr::from(node))
+        });
+        self.register_fn("eval", |ctx, args| {
+            let [expr] = ctx.get_n(args)?;
+            let node = expr.to_node(&ctx.span);
 
-            Ok(Expr::Vector(string.chars().map(Expr::Char).collect()))
+            // quoted code only sees globals, wherever it is evaluated from
+            ctx.eval_scoped(&node, Env::default())
         });
-        scope.register("join", |ctx, args| {
-            let [sep, coll] = ctx.get_n(args)?;
-            let sep = ctx.eval(sep).and_then(|expr| match expr {
-                Expr::Char(c) => Ok(c.to_string()),
-                Expr::String(s) => Ok(s),
-                _ => Err(ctx.error(ErrorKind::InvalidArgument(expr))),
-            })?;
-            let vec = ctx.eval(coll).and_then(|expr| match expr {
-                Expr::List(v) | Expr::Vector(v) => ctx.downcast_all::<String>(&v),
-                Expr::Nil => Ok(vec![]),
-                _ => Err(ctx.error(ErrorKind::InvalidArgument(expr))),
-            })?;
-
-            Ok(Expr::String(vec.join(&sep)))
-        });
-        scope.register("println", |ctx, args| {
-            let string = ctx
-                .eval_args(args)
-                .and_then(|v| ctx.downcast_all::<String>(&v))?
-                .join(" ");
-            if std::io::stdout().is_terminal() {
-                println!("{string}\x1b[m");
-                Ok(Expr::Nil)
-            } else {
-                Ok(Expr::Display(string))
-            }
+        self.register_fn("apply", |ctx, args| {
+            let (f, rest) = args
+                .split_first()
+                .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+            let (last, init) = rest
+                .split_last()
+                .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+            let last = match last {
+                Expr::List(v) | Expr::Vector(v) => &v[..],
+                Expr::Nil => &[],
+                _ => return Err(ctx.error(ErrorKind::InvalidArgument(last.clone()))),
+            };
+
+            ctx.call(f, init.iter().chain(last).cloned().collect())
         });
-        scope.register("quote", |ctx, args| {
-            let [node] = ctx.get_n(args)?;
-            Ok(Expr::from(node))
+        self.register_fn("list", |ctx, args| {
+            ctx.alloc(args.len())?;
+            Ok(Expr::List(args.into()))
         });
-        scope.register("list", |ctx, args| Ok(Expr::List(ctx.eval_args(args)?)));
-        scope.register("vector", |ctx, args| Ok(Expr::Vector(ctx.eval_args(args)?)));
-        scope.register("vec", |ctx, args| {
-            let [node] = ctx.get_n(args)?;
-            let vec = match ctx.eval(node)? {
-                Expr::List(v) | Expr::Vector(v) => v,
-                Expr::Nil => Vec::new(),
-                Expr::String(s) => s.chars().map(Expr::Char).collect(),
-                e => return Err(ctx.error(ErrorKind::InvalidArgument(e))),
+        self.register_fn("vector", |ctx, args| {
+            ctx.alloc(args.len())?;
+            Ok(Expr::Vector(args.into()))
+        });
+        self.register_fn("vec", |ctx, args| {
+            let [coll] = ctx.get_n(args)?;
+            let vec = match coll {
+                Expr::List(v) | Expr::Vector(v) => v.clone(),
+                Expr::Nil => Rc::default(),
+                Expr::String(s) => {
+                    ctx.alloc(s.len())?;
+                    s.chars().map(Expr::Char).collect()
+                }
+                e => return Err(ctx.error(ErrorKind::InvalidArgument(e.clone()))),
             };
 
             Ok(Expr::Vector(vec))
         });
-        scope.register("len", |ctx, args| {
-            let [node] = ctx.get_n(args)?;
-            let len = match ctx.eval(node)? {
+        self.register_fn("len", |ctx, args| {
+            let [coll] = ctx.get_n(args)?;
+            let len = match coll {
                 Expr::List(v) | Expr::Vector(v) => v.len() as f64,
-                Expr::String(s) => s.len() as f64,
+                Expr::String(s) => s.chars().count() as f64,
                 Expr::Nil => 0.,
-                e => return Err(ctx.error(ErrorKind::InvalidArgument(e))),
+                e => return Err(ctx.error(ErrorKind::InvalidArgument(e.clone()))),
             };
 
             Ok(Expr::Number(len))
         });
-        scope.register("nth", |ctx, args| {
+        self.register_fn("nth", |ctx, args| {
             let (coll, nth, default) = match ctx.get_n(args) {
-                Ok([coll, nth, default]) => (ctx.eval(coll)?, ctx.eval(nth)?, ctx.eval(default)?),
+                Ok([coll, nth, default]) => (coll, nth, default.clone()),
                 Err(_) => {
                     let [coll, nth] = ctx.get_n(args)?;
-                    (ctx.eval(coll)?, ctx.eval(nth)?, Expr::Nil)
+                    (coll, nth, Expr::Nil)
                 }
             };
 
+            let chars;
             let coll = match coll {
-                Expr::List(v) | Expr::Vector(v) => v,
-                Expr::Nil => Vec::new(),
-                Expr::String(s) => s.chars().map(Expr::Char).collect(),
-                _ => return Err(ctx.error(ErrorKind::InvalidArgument(coll))),
+                Expr::List(v) | Expr::Vector(v) => &v[..],
+                Expr::Nil => &[],
+                Expr::String(s) => {
+                    chars = s.chars().map(Expr::Char).collect::<Vec<_>>();
+                    chars.as_slice()
+                }
+                _ => return Err(ctx.error(ErrorKind::InvalidArgument(coll.clone()))),
             };
-            let nth = match ctx.downcast::<f64>(&nth)? {
+            let nth = match ctx.downcast::<f64>(nth)? {
                 n if n < 0. => coll.len() - n as usize,
                 n => n as usize,
             };
 
             Ok(coll.get(nth).unwrap_or(&default).clone())
         });
-        scope.register("lambda", lambda);
-        scope.register("λ", lambda);
-        scope.register("fn", |ctx, args| {
+        self.register("lambda", lambda);
+        self.register("λ", lambda);
+        self.register("fn", |ctx, args| {
             let [name, params, body] = ctx.get_n(args)?;
             let name = ctx.downcast::<Symbol>(&Expr::from(name))?;
             let params = match Expr::from(params) {
@@ -180,16 +200,16 @@ impl Scope {
             let body = body.clone();
 
             ctx.globals
-                .insert(name.clone(), Expr::UserFn(UserFn::new(name, params, body)));
+                .insert(name, Expr::UserFn(UserFn::new(name, params, body)));
 
             Ok(Expr::Nil)
         });
-        scope.register("let", |ctx, args| {
+        self.register("let", |ctx, args| {
             let (body, bindings) = args
                 .split_last()
                 .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
 
-            let mut locals = ctx.locals.clone();
+            let mut locals = Vec::with_capacity(bindings.len());
 
             for bind in bindings {
                 match &bind.kind {
@@ -197,15 +217,16 @@ impl Scope {
                         let [var, value] = ctx.get_n(b)?;
                         let var = ctx.downcast::<Symbol>(&Expr::from(var))?;
                         let value = ctx.eval(value)?;
-                        locals.insert(var, value);
+                        locals.push((var, value));
                     }
                     _ => return Err(ctx.error(ErrorKind::InvalidArgument(Expr::from(bind)))),
                 }
             }
 
+            let locals = ctx.locals.extend(locals);
             ctx.eval_scoped(body, locals)
         });
-        scope.register("const", |ctx, args| {
+        self.register("const", |ctx, args| {
             let [name, value] = ctx.get_n(args)?;
             let name = ctx.downcast::<Symbol>(&Expr::from(name))?;
             let value = ctx.eval(value)?;
@@ -214,7 +235,7 @@ impl Scope {
 
             Ok(Expr::Nil)
         });
-        scope.register("if", |ctx, args| match ctx.get_n(args) {
+        self.register("if", |ctx, args| match ctx.get_n(args) {
             Ok([cond, then, or_else]) => {
                 if ctx.eval(cond).and_then(|v| ctx.downcast(&v))? {
                     ctx.eval(then)
@@ -231,7 +252,7 @@ impl Scope {
                 }
             }
         });
-        scope.register("and", |ctx, args| {
+        self.register("and", |ctx, args| {
             for n in args {
                 if !ctx.eval(n).and_then(|v| ctx.downcast(&v))? {
                     return Ok(Expr::Bool(false));
@@ -239,7 +260,7 @@ impl Scope {
             }
             Ok(Expr::Bool(true))
         });
-        scope.register("or", |ctx, args| {
+        self.register("or", |ctx, args| {
             for n in args {
                 if ctx.eval(n).and_then(|v| ctx.downcast(&v))? {
                     return Ok(Expr::Bool(true));
@@ -247,7 +268,7 @@ impl Scope {
             }
             Ok(Expr::Bool(false))
         });
-        scope.register("not", |ctx, args| {
+        self.register("not", |ctx, args| {
             let [node] = ctx.get_n(args)?;
             Ok(Expr::Bool(
                 !ctx.eval(node)
@@ -255,10 +276,81 @@ impl Scope {
                     .unwrap_or(false),
             ))
         });
-        scope.register("parse-num", |ctx, args| {
-            let [v] = ctx.get_n(args)?;
-            let expr = ctx.eval(v)?;
-            let num = match &expr {
+        self.register("assert", |ctx, args| {
+            let [node] = ctx.get_n(args)?;
+            if ctx.eval(node).and_then(|v| ctx.downcast(&v))? {
+                Ok(Expr::Nil)
+            } else {
+                Err(Error::new(
+                    ErrorKind::AssertionFailed(Expr::from(node)),
+                    node.span.clone(),
+                ))
+            }
+        });
+        self.register_fn("assert=", |ctx, args| {
+            let [expected, actual] = ctx.get_n(args)?;
+            if expected == actual {
+                Ok(Expr::Nil)
+            } else {
+                Err(ctx.error(ErrorKind::AssertionNotEqual(
+                    expected.clone(),
+                    actual.clone(),
+                )))
+            }
+        });
+        self.register("deftest", |ctx, args| {
+            let (name, body) = args
+                .split_first()
+                .ok_or_else(|| ctx.error(ErrorKind::MissingArguments))?;
+            let span = name.span.clone();
+            let name = ctx.downcast::<Symbol>(&Expr::from(name))?;
+
+            ctx.tests.retain(|t| t.name != name);
+            ctx.tests.push(Test::new(name, span, body.to_vec()));
+
+            Ok(Expr::Nil)
+        });
+
+        super::record::register(self);
+        super::types::register(self);
+    }
+
+    /// Register the string manipulation and parsing builtins
+    fn strings(&mut self) {
+        self.register_fn("str", |ctx, args| {
+            let parts = ctx.downcast_all::<String>(args)?;
+            ctx.alloc(parts.iter().map(String::len).sum())?;
+
+            Ok(Expr::String(parts.concat().into()))
+        });
+        self.register("chars", |ctx, args| {
+            let [node] = ctx.get_n(args)?;
+            let string = ctx.downcast::<String>(&Expr::from(node))?;
+            ctx.alloc(string.len())?;
+
+            Ok(Expr::Vector(string.chars().map(Expr::Char).collect()))
+        });
+        self.register_fn("join", |ctx, args| {
+            let [sep, coll] = ctx.get_n(args)?;
+            let sep = match sep {
+                Expr::Char(c) => c.to_string(),
+                Expr::String(s) => s.to_string(),
+                _ => return Err(ctx.error(ErrorKind::InvalidArgument(sep.clone()))),
+            };
+            let vec = match coll {
+                Expr::List(v) | Expr::Vector(v) => ctx.downcast_all::<String>(v)?,
+                Expr::Nil => vec![],
+                _ => return Err(ctx.error(ErrorKind::InvalidArgument(coll.clone()))),
+            };
+
+            let len = vec.iter().map(String::len).sum::<usize>();
+            ctx.alloc(len.saturating_add(sep.len().saturating_mul(vec.len().saturating_sub(1))))?;
+
+            Ok(Expr::String(vec.join(&sep).into()))
+        });
+        self.register_fn("parse-num", |ctx, args| {
+            let [expr] = ctx.get_n(args)?;
+            let num = match expr {
                 Expr::String(s) => s.parse::<f64>().ok(),
                 Expr::Char(c) => c.to_digit(10).map(f64::from),
                 Expr::Number(n) => Some(*n),
@@ -266,31 +358,225 @@ impl Scope {
             };
 
             Ok(Expr::Number(num.ok_or_else(|| {
-                ctx.error(ErrorKind::InvalidArgument(expr))
+                ctx.error(ErrorKind::InvalidArgument(expr.clone()))
             })?))
         });
+        self.register_fn("number->string", |ctx, args| {
+            let (n, radix) = match ctx.get_n(args) {
+                Ok([n, radix]) => (n, Some(radix)),
+                Err(_) => {
+                    let [n] = ctx.get_n(args)?;
+                    (n, None)
+                }
+            };
+            let Expr::Number(n) = n else {
+                return Err(ctx.error(ErrorKind::InvalidArgument(n.clone())));
+            };
+            let Some(radix) = radix else {
+                return Ok(Expr::String(Expr::Number(*n).to_string().into()));
+            };
 
-        scope
+            let radix = usize::from_expr(radix)
+                .and_then(|r| u32::try_from(r).ok())
+                .filter(|r| (2..=36).contains(r))
+                .ok_or_else(|| ctx.error(ErrorKind::InvalidArgument(radix.clone())))?;
+            // every whole number below 2^128 converts exactly, so the digits are those of the value
+            if n.fract() != 0. || !n.is_finite() || n.abs() >= 2f64.powi(128) {
+                return Err(ctx.error(ErrorKind::InvalidArgument(Expr::Number(*n))));
+            }
+
+            // the digits of the magnitude, least significant first
+            let mut digits = Vec::new();
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let mut int = n.abs() as u128;
+            loop {
+                #[allow(clippy::cast_possible_truncation)]
+                let digit = (int % u128::from(radix)) as u32;
+                digits.extend(char::from_digit(digit, radix));
+                int /= u128::from(radix);
+                if int == 0 {
+                    break;
+                }
+            }
+            if *n < 0. {
+                digits.push('-');
+            }
+
+            Ok(Expr::String(
+                digits.into_iter().rev().collect::<String>().into(),
+            ))
+        });
+        self.register_fn("read-string", |ctx, args| {
+            let [s] = ctx.get_n(args)?;
+            let Expr::String(s) = s else {
+                return Err(ctx.error(ErrorKind::InvalidArgument(s.clone())));
+            };
+
+            // every new name read is interned for good, so the source counts against the budget
+            ctx.alloc(s.len())?;
+
+            // like a REPL, only the first form is read, and the rest is never parsed
+            let node = Parser::new(s)
+                .read()
+                .map_err(|e| ctx.error(ErrorKind::InvalidSyntax(e)))?;
+            Ok(node.as_ref().map_or(Expr::Nil, Expr::from))
+        });
+
+        super::strings::register(self);
+        super::format::register(self);
+        #[cfg(feature = "regex")]
+        super::regex::register(self);
+        #[cfg(feature = "json")]
+        super::json::register(self);
     }
 
-    /// Register a builtin function
-    fn register<S: Into<String> + Clone>(&mut self, name: S, callback: Callback) {
+    /// Register the builtins that use the standard streams
+    fn io(&mut self) {
+        self.register_fn("println", |ctx, args| {
+            let string = ctx.downcast_all::<String>(args)?.join(" ");
+            ctx.io(ctx.streams.println(&string))?;
+            Ok(Expr::Nil)
+        });
+        self.register_fn("eprintln", |ctx, args| {
+            let string = ctx.downcast_all::<String>(args)?.join(" ");
+            ctx.io(ctx.streams.eprintln(&string))?;
+            Ok(Expr::Nil)
+        });
+        self.register_fn("read-line", |ctx, args| {
+            let [] = ctx.get_n(args)?;
+            let line = ctx.io(ctx.streams.read_line())?;
+            ctx.alloc(line.as_ref().map_or(0, String::len))?;
+
+            Ok(line.map_or(Expr::Nil, |l| Expr::String(l.into())))
+        });
+        self.register_fn("read-all", |ctx, args| {
+            let [] = ctx.get_n(args)?;
+            let string = ctx.io(ctx.streams.read_all())?;
+            ctx.alloc(string.len())?;
+
+            Ok(Expr::String(string.into()))
+        });
+        // stdin is read lazily, a line at a time, so input can be handled as it arrives
+        self.register_fn("stdin-lines", |ctx, args| {
+            let [] = ctx.get_n(args)?;
+            Ok(Expr::Opaque(Opaque::new(Lines::new(&ctx.streams))))
+        });
+        self.register_fn("next-line", |ctx, args| {
+            let [lines] = ctx.get_n(args)?;
+            let Some(lines) = Rc::<Lines>::from_expr(lines) else {
+                return Err(ctx.error(ErrorKind::InvalidArgument(lines.clone())));
+            };
+            let line = ctx.io(lines.next())?;
+            ctx.alloc(line.as_ref().map_or(0, String::len))?;
+
+            Ok(line.map_or(Expr::Nil, |l| Expr::String(l.into())))
+        });
+        self.register_fn("prompt", |ctx, args| {
+            let message = ctx.downcast_all::<String>(args)?.join(" ");
+            ctx.io(ctx.streams.print(&message))?;
+            let line = ctx.io(ctx.streams.read_line())?;
+            ctx.alloc(line.as_ref().map_or(0, String::len))?;
+
+            Ok(line.map_or(Expr::Nil, |l| Expr::String(l.into())))
+        });
+    }
+
+    /// Register the builtins that access the process environment
+    fn env(&mut self) {
+        self.insert(
+            Symbol::from("@cmd-args"),
+            Expr::Vector(std::env::args().map(|a| Expr::String(a.into())).collect()),
+        );
+        self.register_fn("getenv", |ctx, args| {
+            let [name] = ctx.get_n(args)?;
+            let Expr::String(name) = name else {
+                return Err(ctx.error(ErrorKind::InvalidArgument(name.clone())));
+            };
+
+            let value = match ctx.vars.get(&**name) {
+                Some(value) => value.clone(),
+                None => std::env::var(&**name).ok(),
+            };
+
+            Ok(value.map_or(Expr::Nil, |v| Expr::String(v.into())))
+        });
+        self.register_fn("setenv", |ctx, args| {
+            let [name, value] = ctx.get_n(args)?;
+            let name = match name {
+                Expr::String(s) if !s.is_empty() && !s.contains(['=', '\0']) => s,
+                _ => return Err(ctx.error(ErrorKind::InvalidArgument(name.clone()))),
+            };
+            // the process environment is shared by every context, so changes are kept per context
+            let value = match value {
+                Expr::Nil => None,
+                Expr::String(s) if !s.contains('\0') => Some(s.to_string()),
+                _ => return Err(ctx.error(ErrorKind::InvalidArgument(value.clone()))),
+            };
+            ctx.vars.insert(name.to_string(), value);
+
+            Ok(Expr::Nil)
+        });
+        self.register_fn("env", |ctx, args| {
+            let [] = ctx.get_n(args)?;
+            let mut vars = std::env::vars_os()
+                .map(|(k, v)| {
+                    (
+                        k.to_string_lossy().into_owned(),
+                        Some(v.to_string_lossy().into_owned()),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>();
+            vars.extend(ctx.vars.clone());
+            let vars = vars
+                .into_iter()
+                .filter_map(|(k, v)| Some((k, v?)))
+                .collect::<Vec<_>>();
+            ctx.alloc(vars.len())?;
+
+            Ok(Expr::List(
+                vars.into_iter()
+                    .map(|(k, v)| {
+                        Expr::List([Expr::String(k.into()), Expr::String(v.into())].into())
+                    })
+                    .collect(),
+            ))
+        });
+    }
+
+    /// Register a special form
+    pub(crate) fn register<S: Into<String> + Clone>(&mut self, name: S, callback: Callback) {
         self.insert(
             Symbol::from(name.clone()),
             Expr::BuiltinFn(BuiltinFn::new(name, callback)),
         );
     }
 
+    /// Register a builtin function
+    pub(crate) fn register_fn<S, F>(&mut self, name: S, callback: F)
+    where
+        S: Into<String> + Clone,
+        F: Fn(&mut Context, &[Expr]) -> Result<Expr, Error> + 'static,
+    {
+        self.insert(
+            Symbol::from(name.clone()),
+            Expr::BuiltinFn(BuiltinFn::function(name, callback)),
+        );
+    }
+
     /// Get a function callback from the scope by name
     #[inline]
     pub(crate) fn get(&self, k: &Symbol) -> Option<&Expr> {
-        self.0.get(k)
+        self.bindings.get(k)
     }
 
     /// Insert a function into the scope
     #[inline]
     pub(crate) fn insert(&mut self, k: Symbol, v: Expr) {
-        self.0.insert(k, v);
+        let special = |v: &Expr| matches!(v, Expr::BuiltinFn(f) if f.is_special());
+        if special(&v) | self.bindings.get(&k).is_some_and(special) {
+            self.generation += 1;
+        }
+        self.bindings.insert(k, v);
     }
 }
 
@@ -305,3 +591,105 @@ fn lambda(ctx: &mut Context, args: &[Node]) -> Result<Expr, Error> {
         body,
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::prelude::{Context, EvalErrorKind, Expr, Parser, Symbol};
+
+    eval_test!(assert_eq: "(assert= 6 (* 2 3))", Ok("nil".to_owned()));
+
+    eval_test!(error_assert_eq: "(assert= [1 2] (vector 1 3))", Err(
+        EvalErrorKind::AssertionNotEqual(
+            Expr::Vector(vec![Expr::Number(1.), Expr::Number(2.)].into()),
+            Expr::Vector(vec![Expr::Number(1.), Expr::Number(3.)].into()),
+        )
+    ));
+
+    #[test]
+    fn deftest() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new("(deftest fails (assert (= 1 2)))").parse()?;
+        let mut ctx = Context::default();
+        ctx.eval(&ast[0])?;
+
+        let [test] = ctx.tests() else {
+            panic!("expected a single test");
+        };
+        assert_eq!(test.name, Symbol::from("fails"));
+        assert!(matches!(
+            test.clone().run(&mut ctx).map_err(|e| e.kind),
+            Err(EvalErrorKind::AssertionFailed(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn env() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(
+            r#"(setenv "FAYE_ENV_TEST" "1") (getenv "FAYE_ENV_TEST") (len (env)) (setenv "FAYE_ENV_TEST" nil) (getenv "FAYE_ENV_TEST")"#,
+        )
+        .parse()?;
+        let mut ctx = Context::new();
+        let res = ast
+            .iter()
+            .map(|n| ctx.eval(n))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(res[1], Expr::String("1".into()));
+        assert!(matches!(res[2], Expr::Number(n) if n > 0.));
+        assert_eq!(res[4], Expr::Nil);
+        // changes stay in the context instead of the process environment
+        assert!(std::env::var("FAYE_ENV_TEST").is_err());
+        let ast = Parser::new(r#"(setenv "FAYE_ENV_TEST" "1")"#).parse()?;
+        ctx.eval(&ast[0])?;
+        assert!(std::env::var("FAYE_ENV_TEST").is_err());
+        Ok(())
+    }
+
+    eval_test!(number_string: r#"(vector (number->string 255 16) (number->string -10 2) (number->string 1.5) ##NaN)"#, Ok(
+        r#"["ff" "-1010" "1.5" ##NaN]"#.to_owned()
+    ));
+
+    eval_test!(number_string_large: "(vector (number->string 1e20 10) (number->string (- 0 1e30) 7))", Ok(
+        r#"["100000000000000000000" "-243230604464041356420206160415330525"]"#.to_owned()
+    ));
+
+    eval_test!(error_number_string_fraction: "(number->string 1.5 16)", Err(
+        EvalErrorKind::InvalidArgument(Expr::Number(1.5))
+    ));
+
+    eval_test!(error_number_string_large: "(number->string 1e40 16)", Err(
+        EvalErrorKind::InvalidArgument(Expr::Number(1e40))
+    ));
+
+    eval_test!(eval_quoted: r#"(eval (list (quote +) 1 (read-string "(* 2 3) ignored")))"#, Ok("7".to_owned()));
+
+    eval_test!(eval_globals_only: "(let (x 1) (eval (quote x)))", Err(
+        EvalErrorKind::UnknownSymbol(Symbol::from("x"))
+    ));
+
+    eval_test!(apply: "(apply + 1 2 [3 4])", Ok("10".to_owned()));
+
+    eval_test!(read_string: r#"(read-string "[:a \"b\" 'c']")"#, Ok(r#"[:a "b" 'c']"#.to_owned()));
+
+    eval_test!(read_string_first: r#"(read-string "(1 2) (")"#, Ok("(1 2)".to_owned()));
+
+    eval_test!(eval_values: "(defrecord P [x]) (fn f [] 1) (const g f) (fn f [] 2) (const h (lambda [x] (* x 2))) (eval (list (quote list) (list g) (list h 21) (P 1) (list if true 3 4)))", Ok(
+        "(1 42 #P{:x 1} 3)".to_owned()
+    ));
+
+    eval_test!(eval_compiled_special: "(eval (list (quote fn) (quote k) [] (list if false 1 2))) (k)", Ok(
+        "2".to_owned()
+    ));
+
+    #[test]
+    fn error_read_string() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new("(read-string \"(1\")").parse()?;
+
+        assert!(matches!(
+            Context::default().eval(&ast[0]).map_err(|e| e.kind),
+            Err(EvalErrorKind::InvalidSyntax(_))
+        ));
+        Ok(())
+    }
+}
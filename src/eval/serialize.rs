@@ -0,0 +1,223 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::Expr;
+use crate::prelude::Symbol;
+
+/// Lists and vectors are serialized as sequences, records as maps, `nil` as unit,
+/// and keywords and symbols as strings tagged with a leading `:` or `'`
+///
+/// Strings that start with `:`, `'` or `\` are escaped with a leading `\`, so they are not
+/// mistaken for tagged values when deserialized
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Number(n) => serializer.serialize_f64(*n),
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::String(s) if s.starts_with([':', '\'', '\\']) => {
+                serializer.serialize_str(&format!("\\{s}"))
+            }
+            Self::String(s) => serializer.serialize_str(s),
+            Self::Char(c) => serializer.serialize_char(*c),
+            Self::Symbol(s) => serializer.serialize_str(&format!("'{s}")),
+            Self::Keyword(k) => serializer.serialize_str(&format!(":{k}")),
+            Self::List(v) | Self::Vector(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for e in v.iter() {
+                    seq.serialize_element(e)?;
+                }
+                seq.end()
+            }
+            Self::Record(r) => {
+                let mut map = serializer.serialize_map(Some(r.fields.len()))?;
+                for (field, value) in r.iter() {
+                    map.serialize_entry(&*field.name(), value)?;
+                }
+                map.end()
+            }
+            Self::Nil => serializer.serialize_unit(),
+            Self::BuiltinFn(_) | Self::UserFn(_) | Self::Closure(_) => Err(ser::Error::custom(
+                format!("cannot serialize function `{self}`"),
+            )),
+            Self::Opaque(v) => Err(ser::Error::custom(format!(
+                "cannot serialize host value `{v}`"
+            ))),
+        }
+    }
+}
+
+/// Strings tagged with a leading `:` or `'` are deserialized as keywords or symbols, and a
+/// leading `\` is dropped from the rest of the strings,
+/// sequences as vectors and maps as association lists of `(key value)` pairs
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ExprVisitor)
+    }
+}
+
+struct ExprVisitor;
+
+impl<'de> Visitor<'de> for ExprVisitor {
+    type Value = Expr;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a faye value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Expr, E> {
+        Ok(Expr::Bool(v))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Expr, E> {
+        Ok(Expr::Number(v as f64))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Expr, E> {
+        Ok(Expr::Number(v as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Expr, E> {
+        Ok(Expr::Number(v))
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Expr, E> {
+        Ok(Expr::Char(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Expr, E> {
+        Ok(if let Some(k) = v.strip_prefix(':') {
+            Expr::Keyword(k.to_owned())
+        } else if let Some(s) = v.strip_prefix('\'') {
+            Expr::Symbol(Symbol::from(s))
+        } else if let Some(s) = v.strip_prefix('\\') {
+            Expr::String(s.into())
+        } else {
+            Expr::String(v.into())
+        })
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Expr, E> {
+        Ok(Expr::Nil)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Expr, E> {
+        Ok(Expr::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Expr, D::Error> {
+        Expr::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Expr, A::Error> {
+        let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(e) = seq.next_element()? {
+            vec.push(e);
+        }
+
+        Ok(Expr::Vector(vec.into()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Expr, A::Error> {
+        let mut pairs = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((k, v)) = map.next_entry::<Expr, Expr>()? {
+            pairs.push(Expr::List(vec![k, v].into()));
+        }
+
+        Ok(if pairs.is_empty() {
+            Expr::Nil
+        } else {
+            Expr::List(pairs.into())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_test::{assert_de_tokens, assert_ser_tokens, assert_ser_tokens_error, Token};
+
+    use crate::prelude::{Context, Expr, Parser};
+
+    fn eval(code: &str) -> Expr {
+        let ast = Parser::new(code).parse().expect("valid code");
+        Context::default().eval(&ast[0]).expect("valid expression")
+    }
+
+    #[test]
+    fn serialize() {
+        assert_ser_tokens(
+            &eval("(list 1 true \"hi\" 'c' :kw (quote sym) [] nil)"),
+            &[
+                Token::Seq { len: Some(8) },
+                Token::F64(1.),
+                Token::Bool(true),
+                Token::Str("hi"),
+                Token::Char('c'),
+                Token::Str(":kw"),
+                Token::Str("'sym"),
+                Token::Seq { len: Some(0) },
+                Token::SeqEnd,
+                Token::Unit,
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn tagged_strings() {
+        let value = eval(r#"(vector ":x" "'x" "\\x" "x\\")"#);
+        let tokens = [
+            Token::Seq { len: Some(4) },
+            Token::Str("\\:x"),
+            Token::Str("\\'x"),
+            Token::Str("\\\\x"),
+            Token::Str("x\\"),
+            Token::SeqEnd,
+        ];
+
+        assert_ser_tokens(&value, &tokens);
+        assert_de_tokens(&value, &tokens);
+    }
+
+    #[test]
+    fn serialize_function() {
+        assert_ser_tokens_error(
+            &eval("(vector 1 +)"),
+            &[Token::Seq { len: Some(2) }, Token::F64(1.)],
+            "cannot serialize function `+`",
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        assert_de_tokens(
+            &eval("(vector 2 \"hi\" :kw (quote sym) nil (list (list \"k\" [false])))"),
+            &[
+                Token::Seq { len: None },
+                Token::U8(2),
+                Token::Str("hi"),
+                Token::String(":kw"),
+                Token::Str("'sym"),
+                Token::None,
+                Token::Map { len: Some(1) },
+                Token::Str("k"),
+                Token::Seq { len: Some(1) },
+                Token::Bool(false),
+                Token::SeqEnd,
+                Token::MapEnd,
+                Token::SeqEnd,
+            ],
+        );
+    }
+}
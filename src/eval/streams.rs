@@ -0,0 +1,196 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    cell::RefCell,
+    io::{BufRead, Read, Write},
+    rc::Rc,
+};
+
+/// A destination for the output of a program, shared with the host
+///
+/// Keep a clone of the sink to read back what was written, for example into a `Vec<u8>`
+pub type Sink = Rc<RefCell<dyn Write>>;
+
+/// A source of input for a program, shared with the host
+///
+/// A `std::io::Cursor` over a string can be used to supply scripted input
+pub type InputSource = Rc<RefCell<dyn BufRead>>;
+
+/// The standard streams of a context, which default to the streams of the process
+#[derive(Clone, Default)]
+pub(crate) struct Streams {
+    pub(crate) stdin: Option<InputSource>,
+    pub(crate) stdout: Option<Sink>,
+    pub(crate) stderr: Option<Sink>,
+}
+
+impl Streams {
+    /// Use the standard streams of the process
+    pub(crate) const fn new() -> Self {
+        Self {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+        }
+    }
+
+    /// Read a line from the input stream without its line ending, or `None` at the end of input
+    pub(crate) fn read_line(&self) -> std::io::Result<Option<String>> {
+        read_line(self.stdin.as_ref())
+    }
+
+    /// Read everything left in the input stream
+    pub(crate) fn read_all(&self) -> std::io::Result<String> {
+        let mut string = String::new();
+        match &self.stdin {
+            Some(source) => source.borrow_mut().read_to_string(&mut string)?,
+            None => std::io::stdin().lock().read_to_string(&mut string)?,
+        };
+        Ok(string)
+    }
+
+    /// Write text to the output stream without a line ending, flushing it so it shows up
+    pub(crate) fn print(&self, text: &str) -> std::io::Result<()> {
+        match &self.stdout {
+            Some(sink) => {
+                let mut sink = sink.borrow_mut();
+                sink.write_all(text.as_bytes())?;
+                sink.flush()
+            }
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(text.as_bytes())?;
+                stdout.flush()
+            }
+        }
+    }
+
+    /// Write a line to the output stream
+    pub(crate) fn println(&self, line: &str) -> std::io::Result<()> {
+        match &self.stdout {
+            Some(sink) => writeln!(sink.borrow_mut(), "{line}"),
+            None => writeln!(std::io::stdout().lock(), "{line}"),
+        }
+    }
+
+    /// Write a line to the error stream
+    pub(crate) fn eprintln(&self, line: &str) -> std::io::Result<()> {
+        match &self.stderr {
+            Some(sink) => writeln!(sink.borrow_mut(), "{line}"),
+            None => writeln!(std::io::stderr().lock(), "{line}"),
+        }
+    }
+}
+
+/// The lines of an input stream, read one at a time with `next-line`
+pub(crate) struct Lines(Option<InputSource>);
+
+impl Lines {
+    /// Read the lines of the input stream of a context
+    pub(crate) fn new(streams: &Streams) -> Self {
+        Self(streams.stdin.clone())
+    }
+
+    /// Read the next line, or `None` at the end of input
+    pub(crate) fn next(&self) -> std::io::Result<Option<String>> {
+        read_line(self.0.as_ref())
+    }
+}
+
+/// Read a line from a source, or the process stdin, without its line ending
+fn read_line(source: Option<&InputSource>) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    let n = match source {
+        Some(source) => source.borrow_mut().read_line(&mut line)?,
+        None => std::io::stdin().lock().read_line(&mut line)?,
+    };
+    if n == 0 {
+        return Ok(None);
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+impl std::fmt::Debug for Streams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Streams")
+            .field(
+                "stdin",
+                &self.stdin.as_ref().map_or("process", |_| "source"),
+            )
+            .field(
+                "stdout",
+                &self.stdout.as_ref().map_or("process", |_| "sink"),
+            )
+            .field(
+                "stderr",
+                &self.stderr.as_ref().map_or("process", |_| "sink"),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::prelude::{ContextBuilder, Expr, Parser};
+
+    #[test]
+    fn output() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(r#"(println "hi" "there") (eprintln "oops")"#).parse()?;
+        let stdout = Rc::<RefCell<Vec<u8>>>::default();
+        let stderr = Rc::<RefCell<Vec<u8>>>::default();
+
+        let mut ctx = ContextBuilder::all().stdout(stdout.clone()).build();
+        ctx.set_stderr(stderr.clone());
+        assert_eq!(ctx.eval(&ast[0])?, Expr::Nil);
+        assert_eq!(ctx.eval(&ast[1])?, Expr::Nil);
+
+        assert_eq!(&*stdout.borrow(), b"hi there\n");
+        assert_eq!(&*stderr.borrow(), b"oops\n");
+        Ok(())
+    }
+
+    #[test]
+    fn input() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new(
+            r#"(prompt "name?") (read-line) (const ls (stdin-lines)) (next-line ls) (read-line) (next-line ls) (read-all)"#,
+        )
+        .parse()?;
+        let stdin = std::io::Cursor::new("faye\r\nsecond\nthird\nfourth");
+        let stdout = Rc::<RefCell<Vec<u8>>>::default();
+
+        let mut ctx = ContextBuilder::all().stdout(stdout.clone()).build();
+        ctx.set_stdin(Rc::new(RefCell::new(stdin)));
+        let res = ast
+            .iter()
+            .map(|n| ctx.eval(n))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(&*stdout.borrow(), b"name?");
+        assert_eq!(
+            res.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                r#""faye""#,
+                r#""second""#,
+                "nil",
+                r#""third""#,
+                r#""fourth""#,
+                "nil",
+                r#""""#
+            ]
+        );
+        Ok(())
+    }
+}
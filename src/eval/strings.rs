@@ -0,0 +1,274 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{Context, Error, ErrorKind, Expr, FromExpr, Scope};
+
+/// Register the string library
+///
+/// Indices and lengths count chars rather than bytes
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register_fn("split", |ctx, args| {
+        let parts = match ctx.get_n(args) {
+            Ok([s, sep]) => {
+                let sep = pattern(ctx, sep)?;
+                as_str(ctx, s)?.split(&*sep).map(str::to_owned).collect()
+            }
+            Err(_) => {
+                let [s] = ctx.get_n(args)?;
+                as_str(ctx, s)?
+                    .split_whitespace()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        strings(ctx, parts)
+    });
+    scope.register_fn("lines", |ctx, args| {
+        let [s] = ctx.get_n(args)?;
+        let lines = as_str(ctx, s)?.lines().map(str::to_owned).collect();
+        strings(ctx, lines)
+    });
+    scope.register_fn("trim", |ctx, args| {
+        let [s] = ctx.get_n(args)?;
+        Ok(Expr::String(as_str(ctx, s)?.trim().into()))
+    });
+    scope.register_fn("upper", |ctx, args| {
+        let [s] = ctx.get_n(args)?;
+        string(ctx, as_str(ctx, s)?.to_uppercase())
+    });
+    scope.register_fn("lower", |ctx, args| {
+        let [s] = ctx.get_n(args)?;
+        string(ctx, as_str(ctx, s)?.to_lowercase())
+    });
+    scope.register_fn("replace", |ctx, args| {
+        let [s, from, to] = ctx.get_n(args)?;
+        let from = pattern(ctx, from)?;
+        let to = match to {
+            Expr::Char(c) => c.to_string(),
+            _ => as_str(ctx, to)?.to_owned(),
+        };
+        string(ctx, as_str(ctx, s)?.replace(&from, &to))
+    });
+    scope.register_fn("starts-with?", |ctx, args| {
+        let [s, prefix] = ctx.get_n(args)?;
+        Ok(Expr::Bool(
+            as_str(ctx, s)?.starts_with(&*pattern(ctx, prefix)?),
+        ))
+    });
+    scope.register_fn("ends-with?", |ctx, args| {
+        let [s, suffix] = ctx.get_n(args)?;
+        Ok(Expr::Bool(
+            as_str(ctx, s)?.ends_with(&*pattern(ctx, suffix)?),
+        ))
+    });
+    scope.register_fn("contains?", |ctx, args| {
+        let [s, needle] = ctx.get_n(args)?;
+        Ok(Expr::Bool(
+            as_str(ctx, s)?.contains(&*pattern(ctx, needle)?),
+        ))
+    });
+    scope.register_fn("index-of", |ctx, args| {
+        let [s, needle] = ctx.get_n(args)?;
+        let s = as_str(ctx, s)?;
+
+        #[allow(clippy::cast_precision_loss)]
+        Ok(s.find(&*pattern(ctx, needle)?)
+            .map_or(Expr::Nil, |i| Expr::Number(s[..i].chars().count() as f64)))
+    });
+    scope.register_fn("substring", |ctx, args| {
+        let (s, start, end) = match ctx.get_n(args) {
+            Ok([s, start, end]) => (s, start, Some(end)),
+            Err(_) => {
+                let [s, start] = ctx.get_n(args)?;
+                (s, start, None)
+            }
+        };
+
+        let s = as_str(ctx, s)?;
+        let len = s.chars().count();
+        let start = index(ctx, start, len)?;
+        let end = match end {
+            Some(end) => index(ctx, end, len)?,
+            None => len,
+        };
+        if end < start {
+            return Err(ctx.error(ErrorKind::InvalidArgument(args[2].clone())));
+        }
+
+        string(ctx, s.chars().skip(start).take(end - start).collect())
+    });
+    scope.register_fn("pad-left", |ctx, args| {
+        let (s, padding) = pad(ctx, args)?;
+        string(ctx, padding + s)
+    });
+    scope.register_fn("pad-right", |ctx, args| {
+        let (s, padding) = pad(ctx, args)?;
+        string(ctx, s.to_owned() + &padding)
+    });
+    scope.register_fn("repeat", |ctx, args| {
+        let [s, n] = ctx.get_n(args)?;
+        let s = match s {
+            Expr::Char(c) => c.to_string(),
+            _ => as_str(ctx, s)?.to_owned(),
+        };
+        let n = index(ctx, n, usize::MAX)?;
+
+        Ok(Expr::String(repeat(ctx, &s, n)?.into()))
+    });
+    scope.register_fn("char->int", |ctx, args| {
+        let [c] = ctx.get_n(args)?;
+        match c {
+            Expr::Char(c) => Ok(Expr::Number(f64::from(u32::from(*c)))),
+            _ => Err(ctx.error(ErrorKind::InvalidArgument(c.clone()))),
+        }
+    });
+    scope.register_fn("int->char", |ctx, args| {
+        let [n] = ctx.get_n(args)?;
+        u32::try_from(index(ctx, n, u32::MAX as usize)?)
+            .ok()
+            .and_then(char::from_u32)
+            .map(Expr::Char)
+            .ok_or_else(|| ctx.error(ErrorKind::InvalidArgument(n.clone())))
+    });
+}
+
+/// Get the contents of a string argument
+fn as_str<'a>(ctx: &Context, expr: &'a Expr) -> Result<&'a str, Error> {
+    match expr {
+        Expr::String(s) => Ok(s),
+        _ => Err(ctx.error(ErrorKind::InvalidArgument(expr.clone()))),
+    }
+}
+
+/// Get a non-empty string or char to search for
+fn pattern(ctx: &Context, expr: &Expr) -> Result<String, Error> {
+    match expr {
+        Expr::String(s) if !s.is_empty() => Ok(s.to_string()),
+        Expr::Char(c) => Ok(c.to_string()),
+        _ => Err(ctx.error(ErrorKind::InvalidArgument(expr.clone()))),
+    }
+}
+
+/// Get a whole number no greater than `max`
+fn index(ctx: &Context, expr: &Expr, max: usize) -> Result<usize, Error> {
+    usize::from_expr(expr)
+        .filter(|&i| i <= max)
+        .ok_or_else(|| ctx.error(ErrorKind::InvalidArgument(expr.clone())))
+}
+
+/// Get the string and padding for `pad-left` and `pad-right`, which pad with spaces by default
+fn pad<'a>(ctx: &mut Context, args: &'a [Expr]) -> Result<(&'a str, String), Error> {
+    let (s, width, fill) = match ctx.get_n(args) {
+        Ok([s, width, fill]) => (s, width, fill),
+        Err(_) => {
+            let [s, width] = ctx.get_n(args)?;
+            (s, width, &Expr::Char(' '))
+        }
+    };
+
+    let s = as_str(ctx, s)?;
+    let Expr::Char(fill) = fill else {
+        return Err(ctx.error(ErrorKind::InvalidArgument(fill.clone())));
+    };
+    let n = index(ctx, width, usize::MAX)?.saturating_sub(s.chars().count());
+
+    Ok((s, repeat(ctx, &fill.to_string(), n)?))
+}
+
+/// Repeat a string, counting it against the allocation budget
+///
+/// Fails instead of aborting the process when there is not enough memory for the result
+pub(crate) fn repeat(ctx: &mut Context, s: &str, n: usize) -> Result<String, Error> {
+    let len = s
+        .len()
+        .checked_mul(n)
+        .ok_or_else(|| ctx.error(ErrorKind::OutOfMemory(usize::MAX)))?;
+    ctx.alloc(len)?;
+
+    let mut out = String::new();
+    out.try_reserve_exact(len)
+        .map_err(|_| ctx.error(ErrorKind::OutOfMemory(len)))?;
+    for _ in 0..n {
+        out.push_str(s);
+    }
+    Ok(out)
+}
+
+/// Return a new string, counting it against the allocation budget
+fn string(ctx: &mut Context, s: String) -> Result<Expr, Error> {
+    ctx.alloc(s.len())?;
+    Ok(Expr::String(s.into()))
+}
+
+/// Return a vector of new strings, counting them against the allocation budget
+fn strings(ctx: &mut Context, v: Vec<String>) -> Result<Expr, Error> {
+    ctx.alloc(v.len() + v.iter().map(String::len).sum::<usize>())?;
+    Ok(Expr::Vector(
+        v.into_iter().map(|s| Expr::String(s.into())).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::prelude::{EvalErrorKind, Expr};
+
+    eval_test!(split: r#"(split "a,b,,c" ',')"#, Ok(r#"["a" "b" "" "c"]"#.to_owned()));
+
+    eval_test!(split_whitespace: "(split \"  so  many\tspaces \")", Ok(
+        r#"["so" "many" "spaces"]"#.to_owned()
+    ));
+
+    eval_test!(case: r#"(str (upper "straße") (lower "ÀB"))"#, Ok(r#""STRASSEàb""#.to_owned()));
+
+    eval_test!(replace: r#"(replace "a-b-c" "-" ", ")"#, Ok(r#""a, b, c""#.to_owned()));
+
+    eval_test!(replace_char: r#"(vector (replace "a-b" '-' '+') (replace "a-b" "-" ""))"#, Ok(
+        r#"["a+b" "ab"]"#.to_owned()
+    ));
+
+    eval_test!(error_replace_number: r#"(replace "abc" "b" 5)"#, Err(
+        EvalErrorKind::InvalidArgument(Expr::Number(5.))
+    ));
+
+    eval_test!(index_of: r#"(index-of "héllo wörld" "wö")"#, Ok("6".to_owned()));
+
+    eval_test!(substring: r#"(substring "héllo wörld" 1 4)"#, Ok(r#""éll""#.to_owned()));
+
+    eval_test!(substring_end: r#"(substring "wörld" 1)"#, Ok(r#""örld""#.to_owned()));
+
+    eval_test!(pad_left: r#"(pad-left "ö" 3 '0')"#, Ok(r#""00ö""#.to_owned()));
+
+    eval_test!(pad_right: r#"(pad-right "long" 2)"#, Ok(r#""long""#.to_owned()));
+
+    eval_test!(len_chars: r#"(len "λö")"#, Ok("2".to_owned()));
+
+    eval_test!(int_char: "(int->char (+ (char->int 'a') 1))", Ok("'b'".to_owned()));
+
+    eval_test!(display_escapes: r#"(vector (str "tab\there" "\"\e\"\u{7}") '\'' '\space')"#, Ok(
+        r#"["tab\there\"\e\"\u{7}" '\'' ' ']"#.to_owned()
+    ));
+
+    eval_test!(error_substring_range: r#"(substring "abc" 2 4)"#, Err(
+        EvalErrorKind::InvalidArgument(Expr::Number(4.))
+    ));
+
+    eval_test!(error_split_empty: r#"(split "abc" "")"#, Err(
+        EvalErrorKind::InvalidArgument(Expr::String("".into()))
+    ));
+
+    eval_test!(error_int_char: "(int->char 55296)", Err(
+        EvalErrorKind::InvalidArgument(Expr::Number(55296.))
+    ));
+
+    eval_test!(error_repeat_huge: r#"(repeat "ab" 1e18)"#, Err(EvalErrorKind::OutOfMemory(
+        2_000_000_000_000_000_000
+    )));
+
+    eval_test!(error_pad_huge: r#"(pad-left "x" 1e18)"#, Err(EvalErrorKind::OutOfMemory(
+        999_999_999_999_999_999
+    )));
+}
@@ -0,0 +1,28 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{Context, Error};
+use crate::prelude::{Node, Span, Symbol};
+
+/// A test case defined with `deftest`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Test {
+    pub name: Symbol,
+    pub span: Span,
+    body: Vec<Node>,
+}
+
+impl Test {
+    /// Create a new test case
+    #[must_use]
+    pub fn new(name: Symbol, span: Span, body: Vec<Node>) -> Self {
+        Self { name, span, body }
+    }
+
+    /// Run the test case, returning the first error it encounters
+    pub fn run(&self, ctx: &mut Context) -> Result<(), Error> {
+        self.body.iter().try_for_each(|n| ctx.eval(n).map(|_| ()))
+    }
+}
@@ -0,0 +1,104 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{ErrorKind, Expr, Scope};
+
+/// type alias for the checks behind the type predicates
+type Predicate = fn(&Expr) -> bool;
+
+/// Register the type predicates and the builtins that look into values and functions
+pub(crate) fn register(scope: &mut Scope) {
+    scope.register_fn("type-of", |ctx, args| {
+        let [value] = ctx.get_n(args)?;
+        Ok(Expr::Keyword(type_name(value)))
+    });
+
+    let predicates: [(&str, Predicate); 10] = [
+        ("number?", |v| matches!(v, Expr::Number(_))),
+        ("string?", |v| matches!(v, Expr::String(_))),
+        ("char?", |v| matches!(v, Expr::Char(_))),
+        ("bool?", |v| matches!(v, Expr::Bool(_))),
+        ("nil?", |v| matches!(v, Expr::Nil)),
+        ("list?", |v| matches!(v, Expr::List(_))),
+        ("vector?", |v| matches!(v, Expr::Vector(_))),
+        ("fn?", |v| {
+            matches!(v, Expr::BuiltinFn(_) | Expr::UserFn(_) | Expr::Closure(_))
+        }),
+        ("keyword?", |v| matches!(v, Expr::Keyword(_))),
+        ("symbol?", |v| matches!(v, Expr::Symbol(_))),
+    ];
+    for (name, predicate) in predicates {
+        scope.register_fn(name, move |ctx, args| {
+            let [value] = ctx.get_n(args)?;
+            Ok(Expr::Bool(predicate(value)))
+        });
+    }
+
+    scope.register_fn("arity", |ctx, args| {
+        let [f] = ctx.get_n(args)?;
+        #[allow(clippy::cast_precision_loss)]
+        match f {
+            Expr::UserFn(f) => Ok(Expr::Number(f.params.len() as f64)),
+            Expr::Closure(f) => Ok(Expr::Number(f.params.len() as f64)),
+            // most builtins take a varying number of arguments
+            Expr::BuiltinFn(f) => Ok(f.arity.map_or(Expr::Nil, |n| Expr::Number(n as f64))),
+            _ => Err(ctx.error(ErrorKind::InvalidArgument(f.clone()))),
+        }
+    });
+    scope.register_fn("fn-name", |ctx, args| {
+        let [f] = ctx.get_n(args)?;
+        match f {
+            Expr::UserFn(f) => Ok(Expr::Symbol(f.name)),
+            Expr::BuiltinFn(f) => Ok(Expr::Symbol(f.name)),
+            Expr::Closure(_) => Ok(Expr::Nil),
+            _ => Err(ctx.error(ErrorKind::InvalidArgument(f.clone()))),
+        }
+    });
+}
+
+/// Get the name of the type of a value
+///
+/// All records are of type `record`, and `record-name` tells the record types apart
+fn type_name(value: &Expr) -> String {
+    match value {
+        Expr::Number(_) => "number",
+        Expr::Bool(_) => "bool",
+        Expr::String(_) => "string",
+        Expr::Char(_) => "char",
+        Expr::Symbol(_) => "symbol",
+        Expr::Keyword(_) => "keyword",
+        Expr::List(_) => "list",
+        Expr::Vector(_) => "vector",
+        Expr::BuiltinFn(_) | Expr::UserFn(_) | Expr::Closure(_) => "fn",
+        Expr::Opaque(o) => o.type_name(),
+        Expr::Record(_) => "record",
+        Expr::Nil => "nil",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::eval_test;
+    use crate::prelude::{EvalErrorKind, Expr};
+
+    eval_test!(type_of: "(defrecord Point [x]) (list (type-of 1) (type-of \"s\") (type-of (Point 1)) (type-of +) (type-of nil))", Ok(
+        "(:number :string :record :fn :nil)".to_owned()
+    ));
+
+    eval_test!(predicates: "(list (nil? nil) (nil? 0) (fn? (lambda [] 1)) (vector? [1]) (list? [1]) (keyword? :a))", Ok(
+        "(true false true true false true)".to_owned()
+    ));
+
+    eval_test!(arity: "(fn add [a b] (+ a b)) (list (arity add) (arity (lambda [x] x)) (arity +))", Ok(
+        "(2 1 nil)".to_owned()
+    ));
+
+    eval_test!(fn_name: "(fn add [a b] (+ a b)) (list (fn-name add) (fn-name str) (fn-name (lambda [] 1)))", Ok(
+        "(add str nil)".to_owned()
+    ));
+
+    eval_test!(error_arity: "(arity 1)", Err(EvalErrorKind::InvalidArgument(Expr::Number(1.))));
+}
@@ -3,43 +3,87 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{Context, Error, ErrorKind, Expr, Scope};
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use super::{Context, Env, Error, ErrorKind, Expr};
 use crate::prelude::{Node, Symbol};
+use crate::vm::{Chunk, Compiler};
 
 /// A user-defined function
 #[derive(Clone, Debug)]
 pub struct UserFn {
     pub(crate) name: Symbol,
-    params: Vec<Symbol>,
-    body: Node,
+    pub(crate) params: Rc<[Symbol]>,
+    body: Rc<Node>,
+    chunk: Rc<RefCell<Option<Rc<Chunk>>>>,
 }
 
 impl UserFn {
     /// Create a new user-defined function
     #[must_use]
     pub fn new(name: Symbol, params: Vec<Symbol>, body: Node) -> Self {
-        Self { name, params, body }
+        Self {
+            name,
+            params: params.into(),
+            body: Rc::new(body),
+            chunk: Rc::default(),
+        }
     }
 
     /// Evaluate the function with the given arguments
     pub(crate) fn eval(&self, ctx: &mut Context, args: &[Node]) -> Result<Expr, Error> {
-        let mut locals = Scope::default();
+        let mut locals = Vec::with_capacity(self.params.len());
         let mut args = args.iter();
 
-        for param in &self.params {
+        for param in self.params.iter() {
             let value = match args.next() {
                 Some(v) => ctx.eval(v)?,
                 None => return Err(ctx.error(ErrorKind::MissingArguments)),
             };
 
-            locals.insert(param.clone(), value);
+            locals.push((*param, value));
         }
 
         if args.next().is_some() {
             return Err(ctx.error(ErrorKind::TooManyArguments));
         }
 
-        ctx.eval_scoped(&self.body, locals)
+        ctx.eval_scoped(&self.body, Env::default().extend(locals))
+    }
+
+    /// Call the function with already evaluated arguments
+    pub(crate) fn call(&self, ctx: &mut Context, args: &[Expr]) -> Result<Expr, Error> {
+        match args.len().cmp(&self.params.len()) {
+            Ordering::Less => return Err(ctx.error(ErrorKind::MissingArguments)),
+            Ordering::Greater => return Err(ctx.error(ErrorKind::TooManyArguments)),
+            Ordering::Equal => {}
+        }
+
+        let locals = self
+            .params
+            .iter()
+            .copied()
+            .zip(args.iter().cloned())
+            .collect();
+        ctx.eval_scoped(&self.body, Env::default().extend(locals))
+    }
+
+    /// Get the compiled body of the function, compiling it on first use
+    ///
+    /// The body is compiled again if a special form was rebound since, as it may no longer be one
+    pub(crate) fn chunk(&self, ctx: &Context) -> Rc<Chunk> {
+        let mut chunk = self.chunk.borrow_mut();
+        match &*chunk {
+            Some(c) if c.generation == ctx.globals.generation => c.clone(),
+            _ => chunk
+                .insert(Rc::new(Compiler::function(
+                    ctx,
+                    &self.params,
+                    &[],
+                    &self.body,
+                )))
+                .clone(),
+        }
     }
 }
 
@@ -1,5 +1,44 @@
 use crate::prelude::{Lexer, TokenKind};
 
+/// Tracks which tokens belong to forms commented out with `#_`, so they can be highlighted as
+/// comments
+#[derive(Default, Clone, Copy)]
+pub struct DatumComments {
+    /// The datum comments still waiting for a form to skip
+    pending: usize,
+    /// How deep into the skipped form the tokens are
+    depth: usize,
+}
+
+impl DatumComments {
+    /// Check whether a token is part of a commented out form, given the tokens in order
+    pub fn skips(&mut self, kind: &TokenKind) -> bool {
+        match kind {
+            TokenKind::Comment(_) => false,
+            TokenKind::DatumComment => {
+                if self.depth == 0 {
+                    self.pending += 1;
+                }
+                false
+            }
+            _ if self.pending == 0 => false,
+            TokenKind::OpenParen | TokenKind::OpenBracket => {
+                self.depth += 1;
+                true
+            }
+            kind => {
+                if matches!(kind, TokenKind::CloseParen | TokenKind::CloseBracket) {
+                    self.depth = self.depth.saturating_sub(1);
+                }
+                if self.depth == 0 {
+                    self.pending -= 1;
+                }
+                true
+            }
+        }
+    }
+}
+
 /// A highlighter for faye code
 #[derive(Default, Clone, Copy)]
 pub struct Highlighter {
@@ -31,10 +70,12 @@ impl Highlighter {
 
         let mut is_fn = false;
         let mut start = 0;
+        let mut comments = DatumComments::default();
         for res in Lexer::new(snippet) {
             let color = match &res {
+                Ok(t) if comments.skips(&t.kind) => "\x1b[3;90m",
                 Ok(t) => match t.kind {
-                    TokenKind::Comment(_) => "\x1b[3;90m",
+                    TokenKind::Comment(_) | TokenKind::DatumComment => "\x1b[3;90m",
                     TokenKind::OpenParen
                     | TokenKind::CloseParen
                     | TokenKind::OpenBracket
@@ -60,6 +101,7 @@ impl Highlighter {
                     }
                     TokenKind::Number(_) => "\x1b[0;36m",
                     TokenKind::String(_) | TokenKind::Char(_) => "\x1b[0;33m",
+                    TokenKind::Regex(_) => "\x1b[0;93m",
                     TokenKind::Bool(_) | TokenKind::Nil => "\x1b[3;32m",
                     TokenKind::Symbol(_) if is_fn => "\x1b[0;35m",
                     TokenKind::Symbol(_) => "\x1b[0;37m",
@@ -23,11 +23,14 @@ impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.kind {
             ErrorKind::InvalidNumber(n) => write!(f, "`{n}` is not a valid numeric literal"),
-            ErrorKind::InvalidEscape(c) => write!(f, "Unknown escape sequence `\\{c}` in string"),
+            ErrorKind::InvalidEscape(seq) => write!(f, "Invalid escape sequence `\\{seq}`"),
             ErrorKind::InvalidString => write!(f, "Invalid string literal"),
             ErrorKind::UnclosedString => write!(f, "Unclosed string literal"),
             ErrorKind::InvalidChar => write!(f, "Invalid character literal"),
             ErrorKind::UnclosedChar => write!(f, "Unclosed character literal"),
+            ErrorKind::UnclosedRegex => write!(f, "Unclosed regular expression literal"),
+            ErrorKind::UnclosedComment => write!(f, "Unclosed block comment"),
+            ErrorKind::InvalidRegex(msg) => write!(f, "Invalid regular expression: {msg}"),
         }
     }
 }
@@ -38,9 +41,12 @@ impl std::error::Error for Error {}
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ErrorKind {
     InvalidNumber(String),
-    InvalidEscape(char),
+    InvalidEscape(String),
     InvalidString,
     UnclosedString,
     InvalidChar,
     UnclosedChar,
+    UnclosedRegex,
+    InvalidRegex(String),
+    UnclosedComment,
 }
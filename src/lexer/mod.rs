@@ -3,12 +3,10 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{
-    str::{Chars, FromStr},
-    sync::Arc,
-};
+use std::{ops::Range, str::Chars, sync::Arc};
 
 pub use error::{Error, ErrorKind};
+pub(crate) use symbol::BuildSymbolHasher;
 pub use symbol::Symbol;
 pub use token::{Token, TokenKind};
 
@@ -18,6 +16,40 @@ mod error;
 mod symbol;
 mod token;
 
+/// The chars that can be written by name, as in `'\space'`
+pub(crate) const NAMED_CHARS: [(&str, char); 8] = [
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+    ("escape", '\x1b'),
+    ("backspace", '\x08'),
+    ("formfeed", '\x0c'),
+];
+
+/// Escape a string or char so the lexer reads it back as it was, given its quote char
+pub(crate) fn escape(s: &str, quote: char) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            '\x1b' => escaped.push_str("\\e"),
+            c if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.extend(c.escape_unicode()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 /// A lexer for the parser
 #[derive(Debug)]
 pub struct Lexer<'a> {
@@ -90,13 +122,296 @@ impl Lexer<'_> {
         word
     }
 
-    /// Parse a value from the input or return an error
-    fn parse_or<T: FromStr>(&mut self, err: impl Fn(String) -> ErrorKind) -> Result<T, Error> {
-        let span = self.span();
+    /// Read a number, which may have a sign, a `0x`, `0o` or `0b` prefix, `_` separators, a
+    /// fraction and an exponent
+    fn read_number(&mut self) -> Result<f64, Error> {
+        let start = self.span();
         let word = self.read_word();
+        let invalid = |bytes: Range<usize>| {
+            let bytes = start.bytes.start + bytes.start..start.bytes.start + bytes.end;
+            Error::new(
+                ErrorKind::InvalidNumber(word.clone()),
+                Span::new(bytes, start.source.clone()),
+            )
+        };
+        // the span of the char at a byte offset, or of the last char at the end of the word
+        let char_at = |i: usize| match word[i..].chars().next() {
+            Some(c) => i..i + c.len_utf8(),
+            None => word.len() - word.chars().last().map_or(0, char::len_utf8)..word.len(),
+        };
+
+        let sign = usize::from(word.starts_with(['+', '-']));
+        let negative = word.starts_with('-');
+        let radix = match word.get(sign..sign + 2) {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            let digits = sign + 2;
+            let mut n = 0.;
+            let mut empty = true;
+            for (i, c) in word[digits..].char_indices() {
+                if c == '_' {
+                    continue;
+                }
+                let digit = c
+                    .to_digit(radix)
+                    .ok_or_else(|| invalid(char_at(digits + i)))?;
+                n = n * f64::from(radix) + f64::from(digit);
+                empty = false;
+            }
+            if empty {
+                return Err(invalid(sign..word.len()));
+            }
+
+            return Ok(if negative { -n } else { n });
+        }
+
+        #[derive(Clone, Copy, PartialEq)]
+        enum Part {
+            Integer,
+            Fraction,
+            ExponentSign,
+            Exponent,
+        }
+
+        let mut part = Part::Integer;
+        let mut cleaned = String::with_capacity(word.len());
+        for (i, c) in word.char_indices().skip(sign) {
+            part = match (part, c) {
+                (_, '_') if part != Part::ExponentSign => continue,
+                (Part::ExponentSign, '0'..='9') => Part::Exponent,
+                (_, '0'..='9') => part,
+                (Part::Integer, '.') => Part::Fraction,
+                (Part::Integer | Part::Fraction, 'e' | 'E') => Part::ExponentSign,
+                (Part::ExponentSign, '+' | '-') => Part::Exponent,
+                _ => return Err(invalid(char_at(i))),
+            };
+            cleaned.push(c);
+        }
+
+        let n: f64 = cleaned.parse().map_err(|_| invalid(char_at(word.len())))?;
+        Ok(if negative { -n } else { n })
+    }
+
+    /// Read a special float, as in `##NaN`, `##Inf` or `##-Inf`
+    fn read_special_number(&mut self) -> Result<f64, Error> {
+        let span = self.span();
+        match self.read_word().as_str() {
+            "##NaN" => Ok(f64::NAN),
+            "##Inf" => Ok(f64::INFINITY),
+            "##-Inf" => Ok(f64::NEG_INFINITY),
+            word => Err(Error::new(
+                ErrorKind::InvalidNumber(word.to_owned()),
+                span.join(&self.span()),
+            )),
+        }
+    }
+
+    /// Read an escape sequence after its backslash, or return `None` at the end of the input
+    fn read_escape(&mut self, start: Span) -> Result<Option<char>, Error> {
+        let Some(c) = self.advance() else {
+            return Ok(None);
+        };
+
+        let c = match c {
+            '"' | '\'' | '\\' => Some(c),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '0' => Some('\0'),
+            'e' => Some('\x1b'),
+            // an ASCII char, as in `\x41`
+            'x' => {
+                let mut digits = String::new();
+                while digits.len() < 2 && self.current().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    digits.extend(self.advance());
+                }
+                u8::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|&n| digits.len() == 2 && n.is_ascii())
+                    .map(char::from)
+            }
+            // any unicode scalar value, as in `\u{1F98B}`
+            'u' if self.current() == Some('{') => {
+                self.advance();
+                let mut digits = String::new();
+                while digits.len() < 6 && self.current().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    digits.extend(self.advance());
+                }
+                if self.current() == Some('}') {
+                    self.advance();
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        c.map(Some).ok_or_else(|| {
+            let span = start.join(&self.span());
+            let seq = self.source.contents()[span.bytes.start + 1..span.bytes.end].to_owned();
+            Error::new(ErrorKind::InvalidEscape(seq), span)
+        })
+    }
+
+    /// Read the name of a char after its backslash, as in `'\space'`, leaving the input as it
+    /// was if it is not one
+    fn read_named_char(&mut self) -> Option<char> {
+        let (input, byte) = (self.input.clone(), self.byte);
+        let mut name = String::new();
+        while let Some(c) = self.current().filter(char::is_ascii_alphabetic) {
+            name.push(c);
+            self.advance();
+        }
+
+        let c = NAMED_CHARS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|&(_, c)| c);
+        if c.is_none() {
+            (self.input, self.byte) = (input, byte);
+        }
+        c
+    }
+
+    /// Check whether the input starts with a raw string, as in `r"..."` or `r#"..."#`
+    fn is_raw_string(&self) -> bool {
+        let rest = &self.input.as_str()[1..];
+        rest.trim_start_matches('#').starts_with('"')
+    }
+
+    /// Read a raw string, which has no escapes and ends at a quote followed by as many `#` as
+    /// it started with
+    fn read_raw_string(&mut self, span: Span) -> Result<TokenKind, Error> {
+        self.advance();
+        let mut hashes = 0;
+        while self.current() == Some('#') {
+            hashes += 1;
+            self.advance();
+        }
+        self.advance();
+        let open_span = span.clone().join(&self.span());
+
+        let closing = format!("\"{}", "#".repeat(hashes));
+        let Some(end) = self.input.as_str().find(&closing) else {
+            while self.advance().is_some() {}
+            return Err(Error::new(ErrorKind::UnclosedString, open_span));
+        };
+        let string = self.input.as_str()[..end].to_owned();
+        for _ in 0..string.chars().count() + 1 + hashes {
+            self.advance();
+        }
+
+        self.end_string(span)?;
+        Ok(TokenKind::String(string))
+    }
+
+    /// Check that a string is followed by a separator
+    fn end_string(&mut self, span: Span) -> Result<(), Error> {
+        if self.current().is_some_and(|c| !c.is_separator()) {
+            self.read_word();
+            return Err(Error::new(
+                ErrorKind::InvalidString,
+                span.join(&self.span()),
+            ));
+        }
 
-        word.parse()
-            .map_err(|_| Error::new(err(word), span.join(&self.span())))
+        Ok(())
+    }
+
+    /// Read a block comment, as in `#| ... |#`, which may have other block comments inside it
+    fn read_block_comment(&mut self, span: Span) -> Result<TokenKind, Error> {
+        self.advance();
+        self.advance();
+        let open_span = span.join(&self.span());
+        let start = self.byte;
+
+        let mut depth = 1;
+        loop {
+            match self.advance() {
+                Some('|') if self.current() == Some('#') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let end = self.byte - 1;
+                        self.advance();
+                        let comment = self.source.contents()[start..end].to_owned();
+                        return Ok(TokenKind::Comment(comment));
+                    }
+                    self.advance();
+                }
+                Some('#') if self.current() == Some('|') => {
+                    depth += 1;
+                    self.advance();
+                }
+                Some(_) => {}
+                None => return Err(Error::new(ErrorKind::UnclosedComment, open_span)),
+            }
+        }
+    }
+
+    /// Read a regular expression literal, which keeps its backslashes except in `\"`
+    fn read_regex(&mut self, span: Span) -> Result<TokenKind, Error> {
+        self.advance();
+        self.advance();
+        let open_span = span.clone().join(&self.span());
+
+        // the byte offset in the source of each byte of the pattern, for pointing at errors
+        let mut pattern = String::new();
+        let mut offsets = Vec::new();
+        loop {
+            let start = self.byte;
+            let c = match self.advance() {
+                Some('"') => break,
+                Some('\\') if self.current() == Some('"') => {
+                    self.advance();
+                    '"'
+                }
+                Some('\\') => {
+                    pattern.push('\\');
+                    offsets.push(start);
+                    match self.advance() {
+                        Some(c) => c,
+                        None => return Err(Error::new(ErrorKind::UnclosedRegex, open_span)),
+                    }
+                }
+                Some(c) => c,
+                None => return Err(Error::new(ErrorKind::UnclosedRegex, open_span)),
+            };
+            let start = self.byte - c.len_utf8();
+            pattern.push(c);
+            offsets.extend(std::iter::repeat_n(start, c.len_utf8()));
+        }
+        offsets.push(self.byte - 1);
+
+        #[cfg(feature = "regex")]
+        if let Err(e) = regex_syntax::Parser::new().parse(&pattern) {
+            let (kind, bytes) = match &e {
+                regex_syntax::Error::Parse(e) => (e.kind().to_string(), *e.span()),
+                regex_syntax::Error::Translate(e) => (e.kind().to_string(), *e.span()),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidRegex(e.to_string()),
+                        span.join(&self.span()),
+                    ))
+                }
+            };
+            let offset = |i: usize| offsets[i.min(offsets.len() - 1)];
+            let (start, end) = (offset(bytes.start.offset), offset(bytes.end.offset));
+            return Err(Error::new(
+                ErrorKind::InvalidRegex(kind),
+                Span::new(start..end.max(start + 1), self.source.clone()),
+            ));
+        }
+
+        self.end_string(span)?;
+        Ok(TokenKind::Regex(pattern))
     }
 
     /// Read the next token from the input
@@ -129,10 +444,25 @@ impl Lexer<'_> {
                 self.advance();
                 TokenKind::CloseBracket
             }
-            '0'..='9' => TokenKind::Number(self.parse_or(ErrorKind::InvalidNumber)?),
-            '+' | '-' if matches!(self.peek(1), Some('0'..='9')) => {
-                TokenKind::Number(self.parse_or(ErrorKind::InvalidNumber)?)
+            '0'..='9' => TokenKind::Number(self.read_number()?),
+            '.' if matches!(self.peek(1), Some('0'..='9')) => {
+                TokenKind::Number(self.read_number()?)
             }
+            '+' | '-'
+                if matches!(
+                    (self.peek(1), self.peek(2)),
+                    (Some('0'..='9'), _) | (Some('.'), Some('0'..='9'))
+                ) =>
+            {
+                TokenKind::Number(self.read_number()?)
+            }
+            '#' if self.peek(1) == Some('|') => self.read_block_comment(span.clone())?,
+            '#' if self.peek(1) == Some('_') => {
+                self.advance();
+                self.advance();
+                TokenKind::DatumComment
+            }
+            '#' if self.peek(1) == Some('#') => TokenKind::Number(self.read_special_number()?),
             ';' => {
                 self.advance();
                 let mut comment = String::new();
@@ -146,7 +476,7 @@ impl Lexer<'_> {
             }
             ':' => {
                 self.advance();
-                TokenKind::Keyword(Symbol(self.read_word()))
+                TokenKind::Keyword(Symbol::from(self.read_word()))
             }
             '"' => {
                 self.advance();
@@ -157,52 +487,50 @@ impl Lexer<'_> {
                     let ch_span = self.span();
                     string.push(match self.advance() {
                         Some('"') => break,
-                        Some('\\') => match self.advance() {
-                            Some(c @ ('"' | '\\')) => c,
-                            Some('n') => '\n',
-                            Some('e') => '\x1b',
-                            Some(c) => {
-                                return Err(Error::new(
-                                    ErrorKind::InvalidEscape(c),
-                                    ch_span.join(&self.span()),
-                                ))
+                        Some('\\') => match self.read_escape(ch_span) {
+                            Ok(Some(c)) => c,
+                            Ok(None) => {
+                                return Err(Error::new(ErrorKind::UnclosedString, quote_span));
+                            }
+                            Err(e) => {
+                                // skip the rest of the string so lexing can carry on after it
+                                while let Some(c) = self.advance() {
+                                    match c {
+                                        '"' => break,
+                                        '\\' => _ = self.advance(),
+                                        _ => {}
+                                    }
+                                }
+                                return Err(e);
                             }
-                            None => return Err(Error::new(ErrorKind::UnclosedString, quote_span)),
                         },
                         Some(c) => c,
                         None => return Err(Error::new(ErrorKind::UnclosedString, quote_span)),
                     });
                 }
 
-                if self.current().is_some_and(|c| !c.is_separator()) {
-                    self.read_word();
-                    return Err(Error::new(
-                        ErrorKind::InvalidString,
-                        span.join(&self.span()),
-                    ));
-                }
-
+                self.end_string(span.clone())?;
                 TokenKind::String(string)
             }
+            'r' if self.is_raw_string() => self.read_raw_string(span.clone())?,
+            '#' if self.peek(1) == Some('"') => self.read_regex(span.clone())?,
             '\'' => {
                 self.advance();
+                let escape_span = self.span();
                 let char = match self.advance() {
-                    Some('\\') => match self.advance() {
-                        Some(c @ ('"' | '\\')) => c,
-                        Some('n') => '\n',
-                        Some('e') => '\x1b',
-                        Some(c) => {
-                            return Err(Error::new(
-                                ErrorKind::InvalidEscape(c),
-                                span.join(&self.span()),
-                            ))
-                        }
-                        None => {
-                            return Err(Error::new(
-                                ErrorKind::UnclosedChar,
-                                span.join(&self.span()),
-                            ));
-                        }
+                    Some('\\') => match self.read_named_char() {
+                        Some(c) => c,
+                        None => match self.read_escape(escape_span).inspect_err(|_| {
+                            self.read_word();
+                        })? {
+                            Some(c) => c,
+                            None => {
+                                return Err(Error::new(
+                                    ErrorKind::UnclosedChar,
+                                    span.join(&self.span()),
+                                ));
+                            }
+                        },
                     },
                     Some(c) => c,
                     _ => return Err(Error::new(ErrorKind::InvalidChar, span.join(&self.span()))),
@@ -320,7 +648,80 @@ mod tests {
         Err(ErrorKind::InvalidNumber("1.1.1".into())),
     ]);
 
+    test!(number_forms: "0xff -0b1010 0o17 1_000_000 .5 -.5e1 1e-9 ##Inf ##-Inf", [
+        Ok(TokenKind::Number(255.)),
+        Ok(TokenKind::Number(-10.)),
+        Ok(TokenKind::Number(15.)),
+        Ok(TokenKind::Number(1_000_000.)),
+        Ok(TokenKind::Number(0.5)),
+        Ok(TokenKind::Number(-5.)),
+        Ok(TokenKind::Number(1e-9)),
+        Ok(TokenKind::Number(f64::INFINITY)),
+        Ok(TokenKind::Number(f64::NEG_INFINITY))
+    ]);
+
+    #[test]
+    fn error_number_spans() {
+        let cases = [
+            ("0xfg", 3..4),
+            ("0b", 0..2),
+            ("12_3x4", 4..5),
+            ("1e+", 2..3),
+            ("-2.5.1", 4..5),
+        ];
+
+        for (code, bytes) in cases {
+            let err = Lexer::new(code).next().unwrap().unwrap_err();
+            assert_eq!(err.kind, ErrorKind::InvalidNumber(code.into()));
+            assert_eq!(err.span.bytes, bytes, "{code}");
+        }
+    }
+
+    test!(block_comment: "#| a #| nested |# b |# 1 #_2", [
+        Ok(TokenKind::Comment(" a #| nested |# b ".into())),
+        Ok(TokenKind::Number(1.)),
+        Ok(TokenKind::DatumComment),
+        Ok(TokenKind::Number(2.))
+    ]);
+
+    test!(error_unclosed_comment: "#| a #| b |#", [Err(ErrorKind::UnclosedComment)]);
+
     test!(error_unclosed_string: "\"hiii", [Err(ErrorKind::UnclosedString)]);
 
     test!(error_invalid_string: "\"hiii\"222", [Err(ErrorKind::InvalidString)]);
+
+    test!(escapes: r#""\t\r\0\'\x41\u{1F98B}" '\u{3bb}' '\x7f'"#, [
+        Ok(TokenKind::String("\t\r\0'A🦋".into())),
+        Ok(TokenKind::Char('λ')),
+        Ok(TokenKind::Char('\x7f'))
+    ]);
+
+    test!(named_chars: r"'\space' '\newline' '\n' '\\'", [
+        Ok(TokenKind::Char(' ')),
+        Ok(TokenKind::Char('\n')),
+        Ok(TokenKind::Char('\n')),
+        Ok(TokenKind::Char('\\'))
+    ]);
+
+    test!(raw_strings: r###"r"C:\faye" r#"say "hi""# r"###, [
+        Ok(TokenKind::String(r"C:\faye".into())),
+        Ok(TokenKind::String(r#"say "hi""#.into())),
+        Ok(TokenKind::Symbol(Symbol::from("r")))
+    ]);
+
+    test!(error_invalid_escape: r#""\q" "\x80" "\u{110000}" '\spaces'"#, [
+        Err(ErrorKind::InvalidEscape("q".into())),
+        Err(ErrorKind::InvalidEscape("x80".into())),
+        Err(ErrorKind::InvalidEscape("u{110000}".into())),
+        Err(ErrorKind::InvalidEscape("s".into()))
+    ]);
+
+    test!(error_unclosed_raw_string: r####"r##"hi"#"####, [Err(ErrorKind::UnclosedString)]);
+
+    test!(regex: r#"#"a\"\d+" "#, [Ok(TokenKind::Regex(r#"a"\d+"#.into()))]);
+
+    // only the parser compiles literals, so highlighting never pays for it
+    test!(regex_too_large: r#"#"\w{1000}{1000}""#, [Ok(TokenKind::Regex(r"\w{1000}{1000}".into()))]);
+
+    test!(error_unclosed_regex: r#"#"\d+\""#, [Err(ErrorKind::UnclosedRegex)]);
 }
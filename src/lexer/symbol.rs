@@ -1,16 +1,120 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+};
+
 /// A symbol used to identify a function or a variable
-#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
-pub struct Symbol(pub String);
+///
+/// Symbols are interned, so copying, hashing and comparing them is as cheap as for an integer
+#[derive(PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Symbol(u32);
+
+/// The global table of interned symbol names, which owns every name
+///
+/// Names are never removed, since a symbol can be copied anywhere. Code that only needs to look
+/// a name up, such as a format placeholder, uses [`Symbol::lookup`] so it does not grow the table
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Mutex::default);
+
+thread_local! {
+    /// Names this thread has already resolved, so only names interned since then take the lock
+    static NAMES: RefCell<Vec<Arc<str>>> = RefCell::default();
+}
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<Arc<str>, u32>,
+    names: Vec<Arc<str>>,
+}
 
 impl Symbol {
     /// Create a new symbol from a string
     pub fn from<T: Into<String>>(s: T) -> Self {
-        Self(s.into())
+        let s = s.into();
+        let mut interner = INTERNER.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(&id) = interner.ids.get(s.as_str()) {
+            return Self(id);
+        }
+
+        let name: Arc<str> = s.into();
+        let id = u32::try_from(interner.names.len()).expect("too many symbols");
+        interner.names.push(name.clone());
+        interner.ids.insert(name, id);
+
+        Self(id)
+    }
+
+    /// Get the symbol with the given name, if one was already created
+    #[must_use]
+    pub fn lookup(s: &str) -> Option<Self> {
+        let interner = INTERNER.lock().unwrap_or_else(PoisonError::into_inner);
+        interner.ids.get(s).copied().map(Self)
+    }
+
+    /// Get the name of the symbol
+    #[must_use]
+    pub fn name(self) -> Arc<str> {
+        let i = self.0 as usize;
+        NAMES.with_borrow_mut(|names| {
+            if i >= names.len() {
+                let interner = INTERNER.lock().unwrap_or_else(PoisonError::into_inner);
+                names.extend_from_slice(&interner.names[names.len()..]);
+            }
+
+            names[i].clone()
+        })
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self == other {
+            return std::cmp::Ordering::Equal;
+        }
+
+        self.name().cmp(&other.name())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Symbol").field(&self.name()).finish()
     }
 }
 
 impl std::fmt::Display for Symbol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A hasher for maps keyed by symbols, which are already unique integers
+#[derive(Default)]
+pub(crate) struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 << 8) | u64::from(b);
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        // spread the bits so that the map's top bits are not all zero
+        self.0 = u64::from(n).wrapping_mul(0x9e37_79b9_7f4a_7c15);
     }
 }
+
+/// A builder for [`SymbolHasher`]
+pub(crate) type BuildSymbolHasher = BuildHasherDefault<SymbolHasher>;
@@ -29,10 +29,14 @@ pub enum TokenKind {
     OpenBracket,
     CloseBracket,
     Comment(String),
+    /// A datum comment, `#_`, which comments out the form after it
+    DatumComment,
     Symbol(Symbol),
     Number(f64),
     Bool(bool),
     String(String),
+    /// A regular expression literal, as in `#"\d+"`
+    Regex(String),
     Char(char),
     Keyword(Symbol),
     Nil,
@@ -9,3 +9,4 @@ pub mod lexer;
 pub mod parser;
 pub mod prelude;
 pub mod span;
+pub mod vm;
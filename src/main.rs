@@ -3,17 +3,17 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-#[cfg(feature = "lsp")]
-use clap::Subcommand;
 use clap::{
     builder::{styling::AnsiColor, Styles},
-    Parser,
+    Parser, Subcommand,
 };
 use faye::prelude::{Context, Expr, Highlighter, Lexer, Parser as FayeParser, Span};
 
 use repl::Repl;
+use runner::Runner;
 
 mod repl;
+mod runner;
 
 // legacy yellow and green clap style
 fn clap_style() -> Styles {
@@ -28,7 +28,6 @@ fn clap_style() -> Styles {
 #[derive(Parser)]
 #[clap(version, author, styles = clap_style())]
 struct FayeArgs {
-    #[cfg(feature = "lsp")]
     #[command(subcommand)]
     command: Option<Command>,
 
@@ -55,12 +54,27 @@ struct FayeArgs {
     /// Highlight matching brackets in the repl
     #[arg(short, long)]
     matching_brackets: bool,
+
+    /// Compile expressions to bytecode and run them on the virtual machine
+    #[arg(long)]
+    vm: bool,
 }
 
-#[cfg(feature = "lsp")]
 #[derive(Subcommand)]
 enum Command {
+    /// Run the tests defined in `*_test.fy` files
+    Test {
+        /// Files or directories to search for tests
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+
+        /// Only run tests whose name contains this string
+        #[arg(short, long)]
+        filter: Option<String>,
+    },
+
     /// Run the language server
+    #[cfg(feature = "lsp")]
     Lsp,
 }
 
@@ -74,20 +88,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = FayeArgs::parse();
     let match_brackets = args.matching_brackets;
 
-    #[cfg(feature = "lsp")]
-    if let Some(Command::Lsp) = args.command {
-        lsp_main();
-        return Ok(());
+    match args.command {
+        Some(Command::Test { paths, filter }) => {
+            let runner = Runner::new(filter, Highlighter::new(match_brackets));
+            if !runner.run(&paths)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        #[cfg(feature = "lsp")]
+        Some(Command::Lsp) => {
+            lsp_main();
+            return Ok(());
+        }
+        None => {}
     }
 
     if let Some(path) = args.file {
         let file = path.trim_start_matches("./").trim_start_matches(".\\");
-        eval(&std::fs::read_to_string(file)?, Some(file), match_brackets);
+        eval(
+            &std::fs::read_to_string(file)?,
+            Some(file),
+            match_brackets,
+            args.vm,
+        );
         return Ok(());
     }
 
     if let Some(code) = args.eval {
-        eval(&code, None, match_brackets);
+        eval(&code, None, match_brackets, args.vm);
         return Ok(());
     }
 
@@ -114,6 +143,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn display_error(hl: Highlighter, span: &Span, err: &impl std::error::Error) {
+    // errors in data read at runtime, such as json, do not point at any code
+    if span.source.contents().is_empty() {
+        return eprintln!("\x1b[1;31merror\x1b[0;1m: {err}\x1b[0m");
+    }
+
     let loc = span.location();
     let end_loc = span.end_location();
 
@@ -132,7 +166,7 @@ fn display_error(hl: Highlighter, span: &Span, err: &impl std::error::Error) {
     );
 }
 
-fn eval(code: &str, path: Option<&str>, match_brackets: bool) {
+fn eval(code: &str, path: Option<&str>, match_brackets: bool, vm: bool) {
     let mut ctx = Context::new();
     let hl = Highlighter::new(match_brackets);
 
@@ -146,9 +180,15 @@ fn eval(code: &str, path: Option<&str>, match_brackets: bool) {
         Err(err) => return display_error(hl, &err.span, &err),
     };
 
-    ast.iter().for_each(|n| match ctx.eval(n) {
-        Ok(Expr::Nil) => {}
-        Ok(res) => println!("{res}"),
-        Err(err) => display_error(hl, &err.span, &err),
+    ast.iter().for_each(|n| {
+        match if vm {
+            ctx.run(&ctx.compile(n))
+        } else {
+            ctx.eval(n)
+        } {
+            Ok(Expr::Nil) => {}
+            Ok(res) => println!("{res}"),
+            Err(err) => display_error(hl, &err.span, &err),
+        }
     });
 }
@@ -41,6 +41,7 @@ pub enum ErrorKind {
     UnexpectedCloseBracket,
     UnclosedBracket,
     UnmatchedBracket,
+    MissingDatum,
     Unreachable,
 }
 
@@ -51,6 +52,7 @@ impl std::fmt::Display for ErrorKind {
             Self::UnexpectedCloseBracket => write!(f, "Unexpected closing bracket"),
             Self::UnclosedBracket => write!(f, "Unclosed parenthesis"),
             Self::UnmatchedBracket => write!(f, "Unmatched bracket"),
+            Self::MissingDatum => write!(f, "Nothing to comment out after `#_`"),
             Self::Unreachable => write!(f, "Unexpected parsing state reached"),
         }
     }
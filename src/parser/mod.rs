@@ -31,56 +31,72 @@ impl<'a> Parser<'a> {
 
     /// Parse the input string into an AST
     pub fn parse(&mut self) -> Result<Vec<Node>, Error> {
+        std::iter::from_fn(|| self.read().transpose()).collect()
+    }
+
+    /// Parse the next form of the input, leaving the rest of it unread
+    pub fn read(&mut self) -> Result<Option<Node>, Error> {
+        // each parent is kept with the datum comments that still have to skip a form in it
         let mut parents = Vec::new();
         let mut cur_node = Node::new(NodeKind::List(Vec::new()), self.lexer.span());
+        let mut skips = Vec::new();
 
         while let Some(token) = self.lexer.read()? {
-            match token.kind {
-                TokenKind::Comment(_) => { /* TODO: maybe add metadata to fns? */ }
-                TokenKind::OpenParen => {
-                    let child = Node::new(NodeKind::List(Vec::new()), token.span);
-                    parents.push(cur_node);
-                    cur_node = child;
+            let node = match token.kind {
+                TokenKind::Comment(_) => continue, // TODO: maybe add metadata to fns?
+                TokenKind::DatumComment => {
+                    skips.push(token.span);
+                    continue;
                 }
-                TokenKind::OpenBracket => {
-                    let child = Node::new(NodeKind::Vector(Vec::new()), token.span);
-                    parents.push(cur_node);
-                    cur_node = child;
+                TokenKind::OpenParen | TokenKind::OpenBracket => {
+                    let kind = if token.kind == TokenKind::OpenParen {
+                        NodeKind::List(Vec::new())
+                    } else {
+                        NodeKind::Vector(Vec::new())
+                    };
+                    let child = Node::new(kind, token.span);
+                    parents.push((std::mem::replace(&mut cur_node, child), skips));
+                    skips = Vec::new();
+                    continue;
                 }
-                TokenKind::CloseParen => {
-                    let mut parent = parents.pop().ok_or_else(|| {
-                        Error::new(ErrorKind::UnexpectedCloseBracket, token.span.clone())
-                    })?;
-                    cur_node.span.extend(&token.span);
-                    if !matches!(cur_node.kind, NodeKind::List(_)) {
-                        return Err(Error::new(ErrorKind::UnmatchedBracket, token.span));
+                TokenKind::CloseParen | TokenKind::CloseBracket => {
+                    if let Some(span) = skips.pop() {
+                        return Err(Error::new(ErrorKind::MissingDatum, span));
                     }
-                    parent.push_node(cur_node)?;
-                    cur_node = parent;
-                }
-                TokenKind::CloseBracket => {
-                    let mut parent = parents.pop().ok_or_else(|| {
+                    let (parent, parent_skips) = parents.pop().ok_or_else(|| {
                         Error::new(ErrorKind::UnexpectedCloseBracket, token.span.clone())
                     })?;
                     cur_node.span.extend(&token.span);
-                    if !matches!(cur_node.kind, NodeKind::Vector(_)) {
+                    let matched = match cur_node.kind {
+                        NodeKind::List(_) => token.kind == TokenKind::CloseParen,
+                        _ => token.kind == TokenKind::CloseBracket,
+                    };
+                    if !matched {
                         return Err(Error::new(ErrorKind::UnmatchedBracket, token.span));
                     }
-                    parent.push_node(cur_node)?;
-                    cur_node = parent;
+                    skips = parent_skips;
+                    std::mem::replace(&mut cur_node, parent)
+                }
+                _ => Node::try_from(token)?,
+            };
+
+            // a form after a datum comment is read and then dropped
+            if skips.pop().is_none() {
+                if parents.is_empty() {
+                    return Ok(Some(node));
                 }
-                _ => cur_node.push_node(Node::try_from(token)?)?,
+                cur_node.push_node(node)?;
             }
         }
 
         if !parents.is_empty() {
             return Err(Error::new(ErrorKind::UnclosedBracket, cur_node.span));
         }
-
-        match cur_node.kind {
-            NodeKind::List(body) => Ok(body),
-            _ => Err(Error::new(ErrorKind::Unreachable, cur_node.span)),
+        if let Some(span) = skips.pop() {
+            return Err(Error::new(ErrorKind::MissingDatum, span));
         }
+
+        Ok(None)
     }
 }
 
@@ -172,13 +188,41 @@ mod tests {
     test!(error_invalid_number: "(+ 1.2.3)", src => Err(Error::new(
         ErrorKind::Lexer(LexerError::new(
             crate::lexer::ErrorKind::InvalidNumber("1.2.3".into()),
-            Span::new(3..8, src.clone())
+            Span::new(6..7, src.clone())
         )),
-        Span::new(3..8, src)
+        Span::new(6..7, src)
+    )));
+
+    test!(datum_comment: "#_(fn [x]\n  x) [#_ #_ 1 [2] 3]", src => Ok(vec![Node::new(
+        NodeKind::Vector(vec![Node::new(NodeKind::Number(3.), Span::new(28..29, src.clone()))]),
+        Span::new(15..30, src)
+    )]));
+
+    test!(error_missing_datum: "(1 #_)", src => Err(Error::new(
+        ErrorKind::MissingDatum,
+        Span::new(3..5, src)
     )));
 
     test!(error_unexpected_close_paren: ")", src => Err(Error::new(
         ErrorKind::UnexpectedCloseBracket,
         Span::new(0..1, src)
     )));
+
+    #[test]
+    fn read_one_form() {
+        let mut parser = Parser::new("#_ 1 [2] (");
+        let src = parser.lexer.source.clone();
+
+        assert_eq!(
+            parser.read(),
+            Ok(Some(Node::new(
+                NodeKind::Vector(vec![Node::new(
+                    NodeKind::Number(2.),
+                    Span::new(6..7, src.clone())
+                )]),
+                Span::new(5..8, src)
+            )))
+        );
+        assert!(parser.read().is_err());
+    }
 }
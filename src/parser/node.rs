@@ -4,6 +4,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::{Error, ErrorKind};
+use crate::eval::Expr;
 use crate::prelude::{Span, Symbol, Token, TokenKind};
 
 /// A node in the AST with a start and end location
@@ -19,12 +20,15 @@ pub enum NodeKind {
     Number(f64),
     Bool(bool),
     String(String),
+    Regex(String),
     Char(char),
     Symbol(Symbol),
     Keyword(Symbol),
     List(Vec<Node>),
     Vector(Vec<Node>),
     Nil,
+    /// A value with no written form, such as a function, put into code built at runtime
+    Value(Expr),
 }
 
 impl Node {
@@ -55,6 +59,16 @@ impl TryFrom<Token> for Node {
             TokenKind::Bool(b) => NodeKind::Bool(b),
             TokenKind::Symbol(s) => NodeKind::Symbol(s),
             TokenKind::String(s) => NodeKind::String(s),
+            TokenKind::Regex(r) => {
+                // the lexer checks the syntax, but a valid pattern can still be too large to
+                // compile. compiling it here also caches it for when the literal is evaluated
+                #[cfg(feature = "regex")]
+                if let Err(e) = crate::eval::regex::compile(&r) {
+                    let kind = crate::lexer::ErrorKind::InvalidRegex(e.to_string());
+                    return Err(crate::lexer::Error::new(kind, token.span).into());
+                }
+                NodeKind::Regex(r)
+            }
             TokenKind::Char(c) => NodeKind::Char(c),
             TokenKind::Keyword(k) => NodeKind::Keyword(k),
             TokenKind::Nil => NodeKind::Nil,
@@ -62,7 +76,10 @@ impl TryFrom<Token> for Node {
             | TokenKind::CloseParen
             | TokenKind::OpenBracket
             | TokenKind::CloseBracket
-            | TokenKind::Comment(_) => return Err(Error::new(ErrorKind::Unreachable, token.span)),
+            | TokenKind::Comment(_)
+            | TokenKind::DatumComment => {
+                return Err(Error::new(ErrorKind::Unreachable, token.span))
+            }
         };
 
         Ok(Self::new(kind, token.span))
@@ -1,4 +1,7 @@
-pub use crate::eval::{Context, Error as EvalError, ErrorKind as EvalErrorKind, Expr};
+pub use crate::eval::{
+    Capability, Context, ContextBuilder, Error as EvalError, ErrorKind as EvalErrorKind, Expr,
+    FromExpr, InputSource, IntoExpr, Sink,
+};
 pub use crate::highlighter::Highlighter;
 pub use crate::lexer::{
     Error as LexerError, ErrorKind as LexerErrorKind, Lexer, Symbol, Token, TokenKind,
@@ -50,7 +50,9 @@ impl pomprt::Editor for FayeEditor {
                 .ctx
                 .list_globals()
                 .into_iter()
-                .filter_map(|Symbol(s)| s.starts_with(word).then_some(s))
+                .map(Symbol::name)
+                .filter(|s| s.starts_with(word))
+                .map(|s| s.to_string())
                 .collect();
 
             Some(pomprt::Completion(start..end, results))
@@ -10,6 +10,11 @@ use editor::FayeEditor;
 mod editor;
 
 fn display_error(hl: Highlighter, span: &Span, err: &impl std::error::Error) {
+    // errors in data read at runtime, such as json, do not point at any code
+    if span.source.contents().is_empty() {
+        return eprintln!("\x1b[1;31merror\x1b[0;1m: {err}\x1b[0m");
+    }
+
     let loc = span.location();
     let end_loc = span.end_location();
 
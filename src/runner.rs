@@ -0,0 +1,165 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use faye::prelude::{Context, EvalError, EvalErrorKind, Highlighter, Node, Parser, Span};
+
+use crate::display_error;
+
+/// A runner for tests defined with `deftest` in `*_test.fy` files
+pub struct Runner {
+    filter: Option<String>,
+    hl: Highlighter,
+    passed: usize,
+    failed: usize,
+    filtered: usize,
+}
+
+impl Runner {
+    /// Create a new test runner that only runs tests whose name contains `filter`
+    #[must_use]
+    pub const fn new(filter: Option<String>, hl: Highlighter) -> Self {
+        Self {
+            filter,
+            hl,
+            passed: 0,
+            failed: 0,
+            filtered: 0,
+        }
+    }
+
+    /// Run every test found in the given paths, returning whether all of them passed
+    pub fn run(mut self, paths: &[String]) -> std::io::Result<bool> {
+        let mut files = Vec::new();
+        for path in paths {
+            discover(Path::new(path), &mut files)?;
+        }
+
+        for file in &files {
+            self.run_file(file);
+        }
+
+        let status = if self.failed == 0 {
+            "\x1b[32mok"
+        } else {
+            "\x1b[31mFAILED"
+        };
+        println!(
+            "\ntest result: {status}\x1b[0m. {} passed; {} failed; {} filtered out",
+            self.passed, self.failed, self.filtered
+        );
+
+        Ok(self.failed == 0)
+    }
+
+    /// Run the tests defined in a single file, each in a fresh context
+    fn run_file(&mut self, path: &Path) {
+        let name = path.display().to_string();
+        let code = match std::fs::read_to_string(path) {
+            Ok(code) => code,
+            Err(err) => return self.fail_file(&name, &Span::default(), &err),
+        };
+
+        let mut parser = Parser::new(&code);
+        parser.set_name(name.clone());
+
+        let ast = match parser.parse() {
+            Ok(ast) => ast,
+            Err(err) => {
+                return self.fail_file(&name, &err.span, &err);
+            }
+        };
+        let tests = match load(&ast, Rc::default()) {
+            Ok(ctx) => ctx.tests().to_vec(),
+            Err(err) => {
+                return self.fail_file(&name, &err.span, &err);
+            }
+        };
+
+        let (tests, filtered): (Vec<_>, Vec<_>) = tests.into_iter().partition(|t| {
+            self.filter
+                .as_ref()
+                .is_none_or(|f| t.name.to_string().contains(f.as_str()))
+        });
+        self.filtered += filtered.len();
+
+        println!("\nrunning {} tests from {name}", tests.len());
+
+        for test in tests {
+            // capture the output of each test, only showing it when the test fails
+            let output = Rc::<RefCell<Vec<u8>>>::default();
+            match load(&ast, output.clone()).and_then(|mut ctx| test.run(&mut ctx)) {
+                Ok(()) => {
+                    println!("test {} ... \x1b[32mok\x1b[0m", test.name);
+                    self.passed += 1;
+                }
+                Err(err) => {
+                    println!("test {} ... \x1b[31mFAILED\x1b[0m", test.name);
+                    self.failed += 1;
+                    display_error(self.hl, &err.span, &err);
+                    if let EvalErrorKind::AssertionNotEqual(expected, actual) = &err.kind {
+                        eprintln!("\x1b[31m    - {expected}\x1b[0m");
+                        eprintln!("\x1b[32m    + {actual}\x1b[0m");
+                    }
+
+                    let output = output.borrow();
+                    if !output.is_empty() {
+                        eprintln!("\x1b[1;36m    output:\x1b[0m");
+                        for line in String::from_utf8_lossy(&output).lines() {
+                            eprintln!("    {line}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Report a file that could not be loaded as a single failure
+    fn fail_file(&mut self, name: &str, span: &Span, err: &impl std::error::Error) {
+        println!("\nloading {name} ... \x1b[31mFAILED\x1b[0m");
+        self.failed += 1;
+        display_error(self.hl, span, err);
+    }
+}
+
+/// Evaluate the top level of a test file in a fresh context, capturing its output
+fn load(ast: &[Node], output: Rc<RefCell<Vec<u8>>>) -> Result<Context, EvalError> {
+    let mut ctx = Context::new();
+    ctx.set_stdout(output.clone());
+    ctx.set_stderr(output);
+    ctx.check(ast).map_or(Ok(ctx), Err)
+}
+
+/// Collect test files from a path, descending into directories
+fn discover(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            discover(&entry, files)?;
+        } else if entry
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.ends_with("_test.fy"))
+        {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
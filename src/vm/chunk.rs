@@ -0,0 +1,85 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::rc::Rc;
+
+use crate::prelude::{Expr, Node, Span, Symbol};
+
+/// A single bytecode instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Push `nil`
+    Nil,
+    /// Push a boolean
+    Bool(bool),
+    /// Push a value from the constant pool
+    Const(u32),
+    /// Push the value of a local slot
+    Local(u32),
+    /// Push the value of a global symbol
+    Global(u32),
+    /// Pop a value into a local slot
+    SetLocal(u32),
+    /// Pop a value into a global symbol and push `nil`
+    Define(u32),
+    /// Create a closure capturing the current locals
+    Lambda(u32),
+    /// Point errors at the head of a call site
+    Span(u32),
+    /// Check the function about to be called, evaluating special forms in place
+    Callee(u32),
+    /// Check that the function being called at a site takes more than this many arguments
+    Arg(u32, u32),
+    /// Call the function below the evaluated arguments of a call site
+    Call(u32),
+    /// Jump to an instruction
+    Jump(u32),
+    /// Pop a boolean and jump to an instruction if it is false
+    JumpIfFalse(u32),
+    /// Pop a boolean and jump to an instruction if it is true
+    JumpIfTrue(u32),
+    /// Return the value on top of the stack from the current function
+    Return,
+}
+
+/// A compiled function body with its constant pool and resolved local slots
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub(crate) code: Vec<Op>,
+    pub(crate) constants: Vec<Expr>,
+    pub(crate) symbols: Vec<(Symbol, Span)>,
+    pub(crate) sites: Vec<Site>,
+    pub(crate) lambdas: Vec<Lambda>,
+    /// Captured variables, stored in the slots right after the parameters
+    pub(crate) captures: Vec<Symbol>,
+    /// Number of local slots, including parameters and captures
+    pub(crate) slots: usize,
+    /// Generation of the global scope that special forms were resolved against
+    pub(crate) generation: u64,
+    /// The expression a top-level chunk was compiled from, to compile it again once stale
+    pub(crate) source: Option<Rc<Node>>,
+}
+
+/// A call site, kept around to report errors and to evaluate special forms
+#[derive(Debug)]
+pub(crate) struct Site {
+    pub(crate) span: Span,
+    pub(crate) argc: usize,
+    /// Unevaluated arguments, passed on if the callee turns out to be a special form
+    pub(crate) args: Vec<Node>,
+    /// Locals visible to a special form at this site
+    pub(crate) locals: Vec<(Symbol, u32)>,
+    /// The instruction right after the call
+    pub(crate) end: u32,
+}
+
+/// A `lambda` expression with its body compiled ahead of time
+#[derive(Debug)]
+pub(crate) struct Lambda {
+    pub(crate) params: Rc<[Symbol]>,
+    pub(crate) body: Rc<Node>,
+    pub(crate) captures: Vec<(Symbol, u32)>,
+    pub(crate) chunk: Rc<Chunk>,
+}
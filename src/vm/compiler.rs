@@ -0,0 +1,340 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{rc::Rc, sync::Arc};
+
+use super::chunk::{Chunk, Lambda, Op, Site};
+use crate::eval::UserFn;
+use crate::prelude::{Context, Expr, Node, NodeKind, Symbol};
+
+/// A compiler that lowers AST nodes into bytecode
+pub(crate) struct Compiler<'a> {
+    ctx: &'a Context,
+    chunk: Chunk,
+    locals: Vec<(Symbol, u32)>,
+    next_slot: u32,
+}
+
+impl<'a> Compiler<'a> {
+    /// Compile a function body, with slots for its parameters followed by its captures
+    pub(crate) fn function(
+        ctx: &'a Context,
+        params: &[Symbol],
+        captures: &[Symbol],
+        body: &Node,
+    ) -> Chunk {
+        let mut compiler = Self {
+            ctx,
+            chunk: Chunk::default(),
+            locals: Vec::new(),
+            next_slot: 0,
+        };
+
+        // params are pushed last so they shadow captures with the same name
+        let captured = captures.iter().copied().zip(params.len() as u32..);
+        let params = params.iter().copied().zip(0..);
+        compiler.locals.extend(captured.chain(params));
+        compiler.next_slot = compiler.locals.len() as u32;
+        compiler.chunk.captures = captures.to_vec();
+        compiler.chunk.slots = compiler.locals.len();
+        compiler.chunk.generation = ctx.globals.generation;
+
+        compiler.node(body);
+        compiler.emit(Op::Return);
+
+        compiler.chunk
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.code.len() - 1
+    }
+
+    /// Point a previously emitted jump at the next instruction
+    fn patch(&mut self, jump: usize) {
+        let target = self.chunk.code.len() as u32;
+        match &mut self.chunk.code[jump] {
+            Op::Jump(t) | Op::JumpIfFalse(t) | Op::JumpIfTrue(t) => *t = target,
+            _ => unreachable!("patched instruction is not a jump"),
+        }
+    }
+
+    fn constant(&mut self, value: Expr) -> u32 {
+        self.chunk.constants.push(value);
+        self.chunk.constants.len() as u32 - 1
+    }
+
+    fn symbol(&mut self, node: &Node, sym: &Symbol) -> u32 {
+        self.chunk.symbols.push((*sym, node.span.clone()));
+        self.chunk.symbols.len() as u32 - 1
+    }
+
+    fn site(&mut self, head: &Node, args: &[Node], special: bool) -> u32 {
+        self.chunk.sites.push(Site {
+            span: head.span.clone(),
+            argc: args.len(),
+            args: if special { Vec::new() } else { args.to_vec() },
+            locals: if special { Vec::new() } else { self.visible() },
+            end: 0,
+        });
+        self.chunk.sites.len() as u32 - 1
+    }
+
+    fn local(&self, sym: &Symbol) -> Option<u32> {
+        self.locals
+            .iter()
+            .rev()
+            .find_map(|(s, slot)| (s == sym).then_some(*slot))
+    }
+
+    /// List the locals in scope, without the ones that have been shadowed
+    fn visible(&self) -> Vec<(Symbol, u32)> {
+        let mut visible: Vec<(Symbol, u32)> = Vec::new();
+        for (sym, slot) in self.locals.iter().rev() {
+            if !visible.iter().any(|(s, _)| s == sym) {
+                visible.push((*sym, *slot));
+            }
+        }
+
+        visible
+    }
+
+    /// Allocate a new local slot
+    fn alloc(&mut self) -> u32 {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        self.chunk.slots = self.chunk.slots.max(self.next_slot as usize);
+        slot
+    }
+
+    fn node(&mut self, node: &Node) {
+        let op = match &node.kind {
+            NodeKind::Symbol(sym) => match self.local(sym) {
+                Some(slot) => Op::Local(slot),
+                None => Op::Global(self.symbol(node, sym)),
+            },
+            NodeKind::List(list) => match list.split_first() {
+                Some((head, args)) => return self.call(head, args),
+                None => Op::Nil,
+            },
+            NodeKind::Nil => Op::Nil,
+            NodeKind::Bool(b) => Op::Bool(*b),
+            _ => Op::Const(self.constant(Expr::from(node))),
+        };
+
+        self.emit(op);
+    }
+
+    /// Get the name of the builtin special form a call head refers to
+    fn special(&self, head: &Node) -> Option<Arc<str>> {
+        let f = match &head.kind {
+            NodeKind::Symbol(sym) if self.local(sym).is_none() => self.ctx.globals.get(sym)?,
+            // code built at runtime holds the special form itself
+            NodeKind::Value(f) => f,
+            _ => return None,
+        };
+
+        match f {
+            Expr::BuiltinFn(f) if f.is_special() => Some(f.name.name()),
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, head: &Node, args: &[Node]) {
+        let compiled = match self.special(head).as_deref() {
+            Some("quote") => self.quote(head, args),
+            Some("if") => self.cond(head, args),
+            Some("and") => self.logic(head, args, true),
+            Some("or") => self.logic(head, args, false),
+            Some("let") => self.bind(head, args),
+            Some("lambda" | "λ") => self.lambda(head, args),
+            Some("fn") => self.define_fn(head, args),
+            Some("const") => self.define(head, args),
+            _ => false,
+        };
+
+        // anything else, including malformed special forms, is left to the callee
+        if !compiled {
+            self.node(head);
+            let site = self.site(head, args, false);
+            self.emit(Op::Callee(site));
+            for (i, arg) in args.iter().enumerate() {
+                self.node(arg);
+                if i + 1 < args.len() {
+                    self.emit(Op::Arg(site, i as u32 + 1));
+                }
+            }
+            self.emit(Op::Call(site));
+            self.chunk.sites[site as usize].end = self.chunk.code.len() as u32;
+        }
+    }
+
+    fn span(&mut self, head: &Node, args: &[Node]) {
+        let site = self.site(head, args, true);
+        self.emit(Op::Span(site));
+    }
+
+    fn quote(&mut self, head: &Node, args: &[Node]) -> bool {
+        let [node] = args else {
+            return false;
+        };
+
+        self.span(head, args);
+        let value = self.constant(Expr::from(node));
+        self.emit(Op::Const(value));
+        true
+    }
+
+    fn cond(&mut self, head: &Node, args: &[Node]) -> bool {
+        let (cond, then, or_else) = match args {
+            [cond, then] => (cond, then, None),
+            [cond, then, or_else] => (cond, then, Some(or_else)),
+            _ => return false,
+        };
+
+        self.span(head, args);
+        self.node(cond);
+        let jump_else = self.emit(Op::JumpIfFalse(0));
+        self.node(then);
+        let jump_end = self.emit(Op::Jump(0));
+        self.patch(jump_else);
+        match or_else {
+            Some(node) => self.node(node),
+            None => _ = self.emit(Op::Nil),
+        }
+        self.patch(jump_end);
+        true
+    }
+
+    /// Compile `and` or `or`, which short-circuit and always return a boolean
+    fn logic(&mut self, head: &Node, args: &[Node], and: bool) -> bool {
+        self.span(head, args);
+
+        let exits = args
+            .iter()
+            .map(|node| {
+                self.node(node);
+                self.emit(if and {
+                    Op::JumpIfFalse(0)
+                } else {
+                    Op::JumpIfTrue(0)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        self.emit(Op::Bool(and));
+        let jump_end = self.emit(Op::Jump(0));
+        exits.into_iter().for_each(|jump| self.patch(jump));
+        self.emit(Op::Bool(!and));
+        self.patch(jump_end);
+        true
+    }
+
+    fn bind(&mut self, head: &Node, args: &[Node]) -> bool {
+        let Some((body, bindings)) = args.split_last() else {
+            return false;
+        };
+        let Some(bindings) = bindings
+            .iter()
+            .map(|bind| match &bind.kind {
+                NodeKind::List(b) => match b.as_slice() {
+                    [Node {
+                        kind: NodeKind::Symbol(var),
+                        ..
+                    }, value] => Some((var, value)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+
+        self.span(head, args);
+
+        // every value is evaluated before any of the bindings come into scope
+        let (depth, next_slot) = (self.locals.len(), self.next_slot);
+        let bound = bindings
+            .into_iter()
+            .map(|(var, value)| {
+                self.node(value);
+                let slot = self.alloc();
+                self.emit(Op::SetLocal(slot));
+                (*var, slot)
+            })
+            .collect::<Vec<_>>();
+        self.locals.extend(bound);
+        self.node(body);
+        self.locals.truncate(depth);
+        self.next_slot = next_slot;
+        true
+    }
+
+    fn lambda(&mut self, head: &Node, args: &[Node]) -> bool {
+        let [params, body] = args else {
+            return false;
+        };
+        let Ok(params) = Vec::<Symbol>::try_from(&Expr::from(params)) else {
+            return false;
+        };
+
+        self.span(head, args);
+
+        let captures = self.visible();
+        let names = captures.iter().map(|(s, _)| *s).collect::<Vec<_>>();
+        let chunk = Self::function(self.ctx, &params, &names, body);
+
+        self.chunk.lambdas.push(Lambda {
+            params: params.into(),
+            body: Rc::new(body.clone()),
+            captures,
+            chunk: Rc::new(chunk),
+        });
+        self.emit(Op::Lambda(self.chunk.lambdas.len() as u32 - 1));
+        true
+    }
+
+    fn define_fn(&mut self, head: &Node, args: &[Node]) -> bool {
+        let [name @ Node {
+            kind: NodeKind::Symbol(sym),
+            ..
+        }, params @ Node {
+            kind: NodeKind::Vector(_),
+            ..
+        }, body] = args
+        else {
+            return false;
+        };
+        let Ok(params) = Vec::<Symbol>::try_from(&Expr::from(params)) else {
+            return false;
+        };
+
+        self.span(head, args);
+
+        let f = UserFn::new(*sym, params, body.clone());
+        let value = self.constant(Expr::UserFn(f));
+        self.emit(Op::Const(value));
+        let sym = self.symbol(name, sym);
+        self.emit(Op::Define(sym));
+        true
+    }
+
+    fn define(&mut self, head: &Node, args: &[Node]) -> bool {
+        let [name @ Node {
+            kind: NodeKind::Symbol(sym),
+            ..
+        }, value] = args
+        else {
+            return false;
+        };
+
+        self.span(head, args);
+        self.node(value);
+        let sym = self.symbol(name, sym);
+        self.emit(Op::Define(sym));
+        true
+    }
+}
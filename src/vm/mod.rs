@@ -0,0 +1,342 @@
+// faye, a pretty lil lisp
+// Copyright (c) 2023 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::rc::Rc;
+
+pub use chunk::{Chunk, Op};
+pub(crate) use compiler::Compiler;
+
+use crate::eval::{Closure, Env, Error, ErrorKind, UserFn};
+use crate::prelude::{Context, Expr, Symbol};
+
+mod chunk;
+mod compiler;
+
+/// A function call in progress
+struct Frame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    base: usize,
+}
+
+/// A stack machine that executes compiled chunks
+pub(crate) struct Vm {
+    stack: Vec<Expr>,
+    frames: Vec<Frame>,
+}
+
+impl Vm {
+    /// Run a top-level chunk, with its captures taken from the context's locals
+    pub(crate) fn run(ctx: &mut Context, chunk: Rc<Chunk>) -> Result<Expr, Error> {
+        let locals = std::mem::take(&mut ctx.locals);
+        let mut vm = Self {
+            stack: chunk
+                .captures
+                .iter()
+                .map(|sym| locals.get(sym).cloned().unwrap_or(Expr::Nil))
+                .collect(),
+            frames: Vec::new(),
+        };
+        vm.stack.resize(chunk.slots, Expr::Nil);
+
+        let res = vm.execute(
+            ctx,
+            Frame {
+                chunk,
+                ip: 0,
+                base: 0,
+            },
+        );
+        ctx.locals = locals;
+        // frames left behind by an error were entered but never returned from
+        for _ in &vm.frames {
+            ctx.leave();
+        }
+        res
+    }
+
+    fn execute(&mut self, ctx: &mut Context, mut frame: Frame) -> Result<Expr, Error> {
+        loop {
+            let op = frame.chunk.code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                Op::Nil => self.stack.push(Expr::Nil),
+                Op::Bool(b) => self.stack.push(Expr::Bool(b)),
+                Op::Const(i) => self.stack.push(frame.chunk.constants[i as usize].clone()),
+                Op::Local(slot) => {
+                    let value = self.stack[frame.base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Global(i) => {
+                    let (sym, span) = &frame.chunk.symbols[i as usize];
+                    let value =
+                        ctx.globals.get(sym).cloned().ok_or_else(|| {
+                            Error::new(ErrorKind::UnknownSymbol(*sym), span.clone())
+                        })?;
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.pop();
+                    self.stack[frame.base + slot as usize] = value;
+                }
+                Op::Define(i) => {
+                    let value = self.pop();
+                    ctx.globals.insert(frame.chunk.symbols[i as usize].0, value);
+                    self.stack.push(Expr::Nil);
+                }
+                Op::Lambda(i) => {
+                    let lambda = &frame.chunk.lambdas[i as usize];
+                    let env = self.env(&frame, &lambda.captures);
+                    self.stack.push(Expr::Closure(Closure::compiled(
+                        env,
+                        lambda.params.clone(),
+                        lambda.body.clone(),
+                        lambda.chunk.clone(),
+                    )));
+                }
+                Op::Span(i) => ctx.span = frame.chunk.sites[i as usize].span.clone(),
+                Op::Callee(i) => {
+                    let site = &frame.chunk.sites[i as usize];
+
+                    match self.stack.last() {
+                        Some(Expr::BuiltinFn(f)) if f.is_special() => {
+                            let f = f.clone();
+                            ctx.span = site.span.clone();
+                            let locals = self.env(&frame, &site.locals);
+                            let outer = std::mem::replace(&mut ctx.locals, locals);
+                            let res = f.eval(ctx, &site.args);
+                            ctx.locals = outer;
+
+                            *self.stack.last_mut().expect("callee is on the stack") = res?;
+                            frame.ip = site.end as usize;
+                        }
+                        Some(Expr::BuiltinFn(_)) => {}
+                        Some(
+                            Expr::UserFn(UserFn { params, .. })
+                            | Expr::Closure(Closure { params, .. }),
+                        ) => {
+                            if params.is_empty() && site.argc > 0 {
+                                return Err(Error::new(
+                                    ErrorKind::TooManyArguments,
+                                    site.span.clone(),
+                                ));
+                            }
+                        }
+                        Some(v) => {
+                            return Err(Error::new(
+                                ErrorKind::InvalidFunction(v.clone()),
+                                site.span.clone(),
+                            ))
+                        }
+                        None => unreachable!("callee is on the stack"),
+                    }
+                }
+                Op::Arg(i, n) => {
+                    let callee = &self.stack[self.stack.len() - n as usize - 1];
+                    if let Expr::UserFn(UserFn { params, .. })
+                    | Expr::Closure(Closure { params, .. }) = callee
+                    {
+                        if params.len() == n as usize {
+                            let site = &frame.chunk.sites[i as usize];
+                            return Err(Error::new(ErrorKind::TooManyArguments, site.span.clone()));
+                        }
+                    }
+                }
+                Op::Call(i) => {
+                    let site = &frame.chunk.sites[i as usize];
+                    ctx.step()
+                        .map_err(|e| Error::new(e.kind, site.span.clone()))?;
+                    let base = self.stack.len() - site.argc;
+
+                    // the callee is left in its slot and dropped when the call returns
+                    let (callee, args) = self.stack[base - 1..].split_first().expect("callee");
+                    let chunk = match callee {
+                        Expr::BuiltinFn(f) => {
+                            ctx.span = site.span.clone();
+                            let res = f.call(ctx, args)?;
+                            self.stack.truncate(base - 1);
+                            self.stack.push(res);
+                            continue;
+                        }
+                        Expr::UserFn(f) => {
+                            if site.argc < f.params.len() {
+                                return Err(Error::new(
+                                    ErrorKind::MissingArguments,
+                                    site.span.clone(),
+                                ));
+                            }
+                            f.chunk(ctx)
+                        }
+                        Expr::Closure(f) => {
+                            if site.argc < f.params.len() {
+                                return Err(Error::new(
+                                    ErrorKind::MissingArguments,
+                                    site.span.clone(),
+                                ));
+                            }
+                            let chunk = f.chunk(ctx);
+                            let env = f.env.clone();
+                            self.stack.extend(
+                                chunk
+                                    .captures
+                                    .iter()
+                                    .map(|sym| env.get(sym).cloned().unwrap_or(Expr::Nil)),
+                            );
+                            chunk
+                        }
+                        v => {
+                            return Err(Error::new(
+                                ErrorKind::InvalidFunction(v.clone()),
+                                site.span.clone(),
+                            ))
+                        }
+                    };
+
+                    ctx.enter()
+                        .map_err(|e| Error::new(e.kind, site.span.clone()))?;
+                    self.stack.resize(base + chunk.slots, Expr::Nil);
+                    let caller = std::mem::replace(&mut frame, Frame { chunk, ip: 0, base });
+                    self.frames.push(caller);
+                }
+                Op::Jump(ip) => frame.ip = ip as usize,
+                Op::JumpIfFalse(ip) => {
+                    if !self.pop_bool(ctx)? {
+                        frame.ip = ip as usize;
+                    }
+                }
+                Op::JumpIfTrue(ip) => {
+                    if self.pop_bool(ctx)? {
+                        frame.ip = ip as usize;
+                    }
+                }
+                Op::Return => {
+                    let res = self.pop();
+                    match self.frames.pop() {
+                        Some(caller) => {
+                            ctx.leave();
+                            self.stack.truncate(frame.base - 1);
+                            self.stack.push(res);
+                            frame = caller;
+                        }
+                        None => return Ok(res),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collect the values of local slots into an environment
+    fn env(&self, frame: &Frame, locals: &[(Symbol, u32)]) -> Env {
+        let bindings = locals
+            .iter()
+            .map(|(sym, slot)| (*sym, self.stack[frame.base + *slot as usize].clone()))
+            .collect();
+
+        Env::default().extend(bindings)
+    }
+
+    fn pop(&mut self) -> Expr {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn pop_bool(&mut self, ctx: &Context) -> Result<bool, Error> {
+        match self.pop() {
+            Expr::Bool(b) => Ok(b),
+            v => Err(ctx.error(ErrorKind::InvalidArgument(v))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::{Context, Parser};
+
+    // evaluates every expression with both the tree-walker and the vm, comparing the results
+    macro_rules! diff {
+        ($name:ident: $input:expr) => {
+            #[test]
+            fn $name() -> Result<(), Box<dyn std::error::Error>> {
+                let ast = Parser::new($input).parse()?;
+                let (mut tree, mut vm) = (Context::default(), Context::default());
+
+                for node in &ast {
+                    let expected = tree.eval(node).map(|v| v.to_string());
+                    let chunk = vm.compile(node);
+                    assert_eq!(vm.run(&chunk).map(|v| v.to_string()), expected);
+                }
+                Ok(())
+            }
+        };
+    }
+
+    diff!(arithmetic: "(+ 1 (* 2 3) (- 10 4 1) (/ 9 3) (% 7 4))");
+
+    diff!(literals: "[1 x \"s\" 'c' :k] () nil true (quote (a [b] c)) (list 1 [2 (+ 1 2)])");
+
+    diff!(conditionals: "(if (< 1 2) :yes :no) (if false 1) (and) (or)
+        (and true (= 1 1) false (undefined)) (or false (> 2 1) (undefined))");
+
+    diff!(let_shadowing: "(let (x 1) (y 2) (let (x (* y 10)) (y x) (list x y)))
+        (let (x 1) (y (+ x 1)) y)");
+
+    diff!(closures: "(fn adder [n] (lambda [x] (+ x n)))
+        (const add2 (adder 2))
+        (add2 40)
+        ((λ [x y] (let (f (lambda [] (* x y))) (f))) 6 7)
+        (let (x 1) ((lambda [x] x) 2))");
+
+    diff!(recursion: include_str!("../../examples/expt.fy"));
+
+    diff!(quicksort: include_str!("../../examples/quicksort.fy"));
+
+    diff!(special_forms_in_place: "(fn f [s] (chars \"abc\"))
+        (f 1)
+        (let (x 1) (not (= x 2)))
+        (let (if-alias if) (if-alias true 1 2))
+        (let (x false) (assert x))");
+
+    diff!(redefined_special_forms: "(fn f [x] (if x 1 2))
+        (f true)
+        (fn if [c a b] b)
+        (f true)
+        (const g (lambda [] (let (x 1) x)))
+        (g)
+        (const let list)
+        (g)");
+
+    #[test]
+    fn stale_chunk() -> Result<(), Box<dyn std::error::Error>> {
+        let ast = Parser::new("(if true 1 2) (fn if [c a b] b)").parse()?;
+        let mut ctx = Context::default();
+
+        let chunk = ctx.compile(&ast[0]);
+        assert_eq!(ctx.run(&chunk)?.to_string(), "1");
+        ctx.eval(&ast[1])?;
+        assert_eq!(ctx.run(&chunk)?.to_string(), "2");
+        Ok(())
+    }
+
+    diff!(error_unknown_symbol: "(+ 1 nope)");
+
+    diff!(error_invalid_function: "(1 2 3) ([1] 0) ((lambda [] 1) 2)");
+
+    diff!(error_arguments: "(fn f [a b] (+ a b))
+        (f 1)
+        (f 1 2 (undefined))
+        (f (undefined))
+        ((lambda [x] x) 1 2)
+        (nth [1])");
+
+    diff!(error_conditions: "(if 1 2 3) (and true nil) (or 1) (let (x 1) (if x 1 2))");
+
+    diff!(error_malformed_special_forms: "(let (x) x) (let) (if true) (fn 1 [] 2)
+        (lambda x) (let (1 2) 3) (const 1 2) (quote)");
+
+    diff!(error_spans: "(fn f [x] (+ x (g x)))
+        (fn g [y] (* y \"two\"))
+        (f 1)
+        (let (h (lambda [z] (f z))) (h (h 1)))");
+}